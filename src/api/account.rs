@@ -1,13 +1,17 @@
-use std::{fmt::format, time::Duration};
+use std::{fmt::format, fs, path::PathBuf, time::Duration};
 
+use log::warn;
 use reqwest::blocking::Client;
 use serde::Deserialize;
 
-use crate::utils::constants::{GET_USER_URL, UPDATE_USER_ONLINE_URL, URL};
+use crate::utils::{
+    constants::{GET_USER_URL, LAUNCHER_DIR, UPDATE_USER_ONLINE_URL, URL},
+    http::client_builder,
+};
 
 use super::auth::Auth;
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct Account {
     id: String,
     login: String,
@@ -20,31 +24,78 @@ pub struct Account {
     client: Client,
     #[serde(skip_deserializing)]
     token: String,
+    // Loaded from cache because server was unreachable
+    #[serde(skip_deserializing)]
+    offline: bool,
 }
 
 impl Account {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
         let token = Auth::load()?.token;
-        let client = Client::builder()
+        let client = client_builder()?
             .timeout(Duration::from_millis(1500))
             .build()?;
-        let resp = client
+        let resp = match client
             .get(format!("{}{}", URL, GET_USER_URL))
             .header("Content-Type", "application/json")
             .header("Authorization", format!("Bearer {token}"))
-            .send()?;
+            .send()
+        {
+            Ok(r) => r,
+            // Server is unreachable, not an authorization problem
+            Err(e) if e.is_connect() || e.is_timeout() => {
+                warn!("Couldn't connect to server, using cached account. Error: {e}");
+                let data = Self::cache_path()
+                    .and_then(|p| fs::read_to_string(p).ok())
+                    .ok_or(e)?;
+
+                let mut ret = serde_json::from_str::<Self>(&data)?;
+                ret.client = client;
+                ret.token = token;
+                ret.offline = true;
+                return Ok(ret);
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         if !resp.status().is_success() {
             return Err(format!("Not authorized. {}", resp.status().to_string()).into());
         }
 
-        let mut ret = serde_json::from_str::<Self>(&resp.text()?)?;
+        let data = resp.text()?;
+        let mut ret = serde_json::from_str::<Self>(&data)?;
         ret.client = client;
         ret.token = token;
 
+        if let Some(path) = Self::cache_path() {
+            fs::write(path, data).unwrap_or_else(|e| {
+                warn!("Couldn't cache account. Error: {e}");
+            });
+        }
+
         Ok(ret)
     }
 
+    fn cache_path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join(LAUNCHER_DIR).join("account.json"))
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn login(&self) -> &str {
+        &self.login
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
     fn update_online(&mut self, online: bool) -> Result<(), Box<dyn std::error::Error>> {
         self.is_online = online;
         self.client
@@ -4,11 +4,13 @@ use std::{
     time::Duration,
 };
 
-use reqwest::blocking::ClientBuilder;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::utils::constants::{LAUNCHER_DIR, LOGIN_URL, URL};
+use crate::utils::{
+    constants::{LAUNCHER_DIR, LOGIN_URL, URL},
+    http::client_builder,
+};
 
 pub const LP_ERROR: &str = "login_error";
 pub const IS_ERROR: &str = "login_is_error";
@@ -23,7 +25,7 @@ pub struct Auth {
 
 impl Auth {
     pub fn send(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let client = ClientBuilder::new()
+        let client = client_builder()?
             .timeout(Duration::from_millis(1500))
             .build()?;
         let res = client
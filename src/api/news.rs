@@ -1,10 +1,10 @@
 use std::{process::exit, time::Duration};
 
-use reqwest::blocking::Client;
 use serde_derive::Deserialize;
 
 use crate::utils::{
     constants::{GET_NEWS_LIST, URL},
+    http::client_builder,
     relaunch::relaunch,
 };
 
@@ -39,7 +39,7 @@ impl News {
     }
 
     pub fn load() -> Result<Vec<Self>, Box<dyn std::error::Error>> {
-        let client = Client::builder()
+        let client = client_builder()?
             .timeout(Duration::from_millis(1500))
             .build()?;
 
@@ -0,0 +1,70 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utils::{
+    constants::{GET_SESSION_TOKEN_URL, LAUNCHER_DIR, URL},
+    http::client_builder,
+};
+
+use super::account::Account;
+
+// Identity passed to the game. The access token is issued by our Yggdrasil-compatible
+// session service, so the game server can verify players on join.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub username: String,
+    pub uuid: String,
+    pub access_token: String,
+    pub user_type: String,
+}
+
+impl Session {
+    fn cache_path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join(LAUNCHER_DIR).join("session.json"))
+    }
+
+    // Last issued session, used to play offline
+    pub fn cached() -> Option<Self> {
+        let data = fs::read_to_string(Self::cache_path()?).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::cache_path().ok_or("OS data dir not found.")?;
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn new(account: &Account) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = client_builder()?
+            .timeout(Duration::from_millis(1500))
+            .build()?;
+        let resp = client
+            .post(format!("{}{}", URL, GET_SESSION_TOKEN_URL))
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", account.token()))
+            .send()?;
+
+        if !resp.status().is_success() {
+            return Err(format!("Couldn't get session token. {}", resp.status()).into());
+        }
+
+        let value = serde_json::from_str::<Value>(&resp.text()?)?;
+        let access_token = value["accessToken"]
+            .as_str()
+            .ok_or("no field: 'accessToken'")?
+            .to_string();
+        let user_type = value["userType"].as_str().unwrap_or("mojang").to_string();
+
+        Ok(Self {
+            username: account.login().to_string(),
+            // Minecraft expects undashed uuid
+            uuid: account.id().replace('-', ""),
+            access_token,
+            user_type,
+        })
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::{
+    launcher::commands::{DownloadProgress, Phase},
+    utils::disk::NotEnoughSpace,
+};
+
+const UNITS: [&str; 4] = ["unit_b", "unit_kb", "unit_mb", "unit_gb"];
+
+// "12.3 MB" or "12,3 МБ" depending on locale
+pub fn format_bytes(bytes: u64, locale: &Value) -> String {
+    let mut value = bytes as f64;
+    let mut unit = 0;
+
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+
+    let number = match unit {
+        0 => bytes.to_string(),
+        _ => format!("{value:.1}").replace('.', locale["decimal_separator"].as_str().unwrap()),
+    };
+
+    format!("{number} {}", locale[UNITS[unit]].as_str().unwrap())
+}
+
+pub fn format_speed(speed: f64, locale: &Value) -> String {
+    format!(
+        "{}{}",
+        format_bytes(speed as u64, locale),
+        locale["unit_per_second"].as_str().unwrap()
+    )
+}
+
+// "1:05" or "1:02:05"
+pub fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    match h {
+        0 => format!("{m}:{s:02}"),
+        _ => format!("{h}:{m:02}:{s:02}"),
+    }
+}
+
+pub fn phase_name(phase: Phase, locale: &Value) -> &str {
+    let key = match phase {
+        Phase::Game => "phase_game",
+        Phase::Mods => "phase_mods",
+        Phase::Modpack => "phase_modpack",
+        Phase::Launcher => "phase_launcher",
+    };
+
+    locale[key].as_str().unwrap()
+}
+
+// Sizes, file count, speed and ETA of download
+pub fn progress_text(progress: &DownloadProgress, locale: &Value) -> String {
+    let mut text = String::new();
+
+    if progress.total_files > 0 {
+        text += &format!(
+            "{}/{} {}, ",
+            progress.files,
+            progress.total_files,
+            locale["main_download_files"].as_str().unwrap()
+        );
+    }
+
+    text += &format!(
+        "{} / {}",
+        format_bytes(progress.bytes, locale),
+        format_bytes(progress.total_bytes, locale)
+    );
+
+    if progress.speed > 0. {
+        text += &format!(", {}", format_speed(progress.speed, locale));
+    }
+
+    if let Some(eta) = progress.eta() {
+        text += &format!(
+            ", {} {}",
+            format_eta(eta),
+            locale["download_left"].as_str().unwrap()
+        );
+    }
+
+    text
+}
+
+// "Not enough disk space: 3.2 GB required, 1.1 GB available."
+pub fn disk_space_text(e: &NotEnoughSpace, locale: &Value) -> String {
+    locale["error_disk_space"]
+        .as_str()
+        .unwrap()
+        .replace("{required}", &format_bytes(e.required, locale))
+        .replace("{available}", &format_bytes(e.available, locale))
+}
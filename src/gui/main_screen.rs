@@ -10,7 +10,9 @@ use serde_json::Value;
 use crate::launcher::commands::Command;
 
 use super::{
+    format::{phase_name, progress_text},
     message_screen::MsgBoxScreen,
+    mods_panel::ModsPanel,
     news_widget::NewsWidget,
     settings_modal::SettingsModal,
     window_frame::{windowframe, WindowFrameData},
@@ -21,6 +23,8 @@ const MAINSCREEN: &str = "MAINSCREEN";
 #[derive(Eq, PartialEq)]
 enum State {
     Updating,
+    // Download which can be paused or cancelled
+    Downloading,
     Idle,
 }
 
@@ -29,9 +33,11 @@ pub struct MainScreen {
     launcher_receiver: Receiver<Command>,
     in_game: Arc<Mutex<bool>>,
     settings_modal: SettingsModal,
+    mods_panel: ModsPanel,
     state: State,
     text: String,
     progress: f32,
+    paused: bool,
     error_msg: MsgBoxScreen,
     wframe: WindowFrameData,
     locale: Value,
@@ -46,13 +52,15 @@ impl MainScreen {
         launcher_receiver: Receiver<Command>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(Self {
+            settings_modal: SettingsModal::new(locale.clone(), logic_sender.clone()),
             logic_sender,
             in_game,
             launcher_receiver,
             state: State::Idle,
             text: locale["main_ready"].as_str().unwrap().to_owned(),
-            settings_modal: SettingsModal::new(locale.clone()),
+            mods_panel: ModsPanel::new(locale.clone()),
             progress: 1.0,
+            paused: false,
             error_msg: MsgBoxScreen::default(),
             wframe: WindowFrameData::new(locale.clone(), "FunnyLauncher").with_account(true),
             news: NewsWidget::new(locale.clone())?,
@@ -93,6 +101,7 @@ impl MainScreen {
                     debug!(target: MAINSCREEN, "CONTINUE command.");
 
                     self.state = State::Idle;
+                    self.paused = false;
                     self.text = self.locale["main_ready"].as_str().unwrap().to_owned()
                 }
                 Command::VALIDATE => {
@@ -101,23 +110,47 @@ impl MainScreen {
                     self.state = State::Updating;
                     self.text = self.locale["main_check"].as_str().unwrap().to_owned()
                 }
-                Command::DOWNLOAD((downloaded, size)) => {
+                Command::DOWNLOAD(progress) => {
                     debug!(target: MAINSCREEN, "DOWNLOAD command.");
 
-                    self.state = State::Updating;
+                    let file = if progress.file.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" ({})", progress.file)
+                    };
+
+                    self.state = State::Downloading;
                     self.text = format!(
-                        "{} {}Mb/{}Mb",
+                        "{} {}{}: {}",
                         self.locale["main_download"].as_str().unwrap(),
-                        downloaded / (1024 * 2),
-                        size / (1024 * 2)
+                        phase_name(progress.phase, &self.locale),
+                        file,
+                        progress_text(&progress, &self.locale)
                     );
-                    self.progress = downloaded as f32 / size as f32;
+                    self.progress = if progress.total_bytes > 0 {
+                        progress.bytes as f32 / progress.total_bytes as f32
+                    } else {
+                        0.0
+                    };
                 }
-                Command::UNZIPING => {
+                Command::UNZIPING(progress) => {
                     debug!(target: MAINSCREEN, "UNZIPING command.");
 
                     self.state = State::Updating;
-                    self.text = self.locale["main_unpack"].as_str().unwrap().to_owned()
+                    self.text = match progress.total_files {
+                        0 => self.locale["main_unpack"].as_str().unwrap().to_owned(),
+                        total => format!(
+                            "{} {}/{}",
+                            self.locale["main_unpack"].as_str().unwrap(),
+                            progress.files,
+                            total
+                        ),
+                    };
+                    self.progress = if progress.total_bytes > 0 {
+                        progress.bytes as f32 / progress.total_bytes as f32
+                    } else {
+                        0.0
+                    };
                 }
                 Command::PLAY => {
                     debug!(target: MAINSCREEN, "PLAY command.");
@@ -131,10 +164,67 @@ impl MainScreen {
                     self.state = State::Idle;
                     self.error_msg = MsgBoxScreen::error("Error", e)
                 }
+                Command::INFO(msg) => {
+                    debug!(target: MAINSCREEN, "INFO command.");
+
+                    self.state = State::Idle;
+                    self.error_msg = MsgBoxScreen::info("Info", msg)
+                }
+                Command::OFFLINE(msg) => {
+                    debug!(target: MAINSCREEN, "OFFLINE command.");
+
+                    self.state = State::Idle;
+                    self.error_msg = MsgBoxScreen::confirm(
+                        "Offline",
+                        format!(
+                            "{msg}\n\n{}",
+                            self.locale["main_offline_prompt"].as_str().unwrap()
+                        ),
+                        self.locale["main_btn_play_offline"].as_str().unwrap(),
+                        self.locale["main_btn_offline_cancel"].as_str().unwrap(),
+                    )
+                }
+                Command::MODMANIFEST(manifest) => {
+                    debug!(target: MAINSCREEN, "MODMANIFEST command.");
+
+                    self.mods_panel.set_manifest(manifest)
+                }
                 _ => (),
             }
         }
     }
+
+    fn send(&self, command: Command) {
+        if self.logic_sender.send(command).is_err() {
+            error!(target: MAINSCREEN, "Error while sending command to control thread.");
+        }
+    }
+
+    // Cancel and pause buttons of running download
+    fn download_controls(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .button(self.locale["main_btn_cancel"].as_str().unwrap())
+            .clicked()
+        {
+            self.send(Command::CANCEL);
+            self.state = State::Updating;
+            self.paused = false;
+            self.text = self.locale["main_cancelling"].as_str().unwrap().to_owned();
+            return;
+        }
+
+        let key = match self.paused {
+            true => "main_btn_resume",
+            false => "main_btn_pause",
+        };
+        if ui.button(self.locale[key].as_str().unwrap()).clicked() {
+            self.send(match self.paused {
+                true => Command::RESUME,
+                false => Command::PAUSE,
+            });
+            self.paused = !self.paused;
+        }
+    }
 }
 
 impl eframe::App for MainScreen {
@@ -200,14 +290,36 @@ impl eframe::App for MainScreen {
                         };
                     }
 
+                    if ui
+                        .add_enabled(
+                            !*in_game_guard,
+                            egui::Button::new(self.locale["main_btn_mods"].as_str().unwrap()),
+                        )
+                        .clicked()
+                    {
+                        self.mods_panel.open();
+                        self.send(Command::MODS);
+                    }
+                    drop(in_game_guard);
+
                     if self.state == State::Idle {
                         ui.with_layout(egui::Layout::left_to_right(egui::Align::LEFT), |ui| {
                             ui.label(&self.text);
                         });
                     } else {
-                        let progress = ProgressBar::new(self.progress)
-                            .show_percentage()
-                            .text(&self.text);
+                        if self.state == State::Downloading {
+                            self.download_controls(ui);
+                        }
+
+                        let text = match self.paused {
+                            true => format!(
+                                "{} - {}",
+                                self.locale["main_paused"].as_str().unwrap(),
+                                self.text
+                            ),
+                            false => self.text.clone(),
+                        };
+                        let progress = ProgressBar::new(self.progress).show_percentage().text(text);
                         ui.add(progress);
                     }
                 });
@@ -221,7 +333,11 @@ impl eframe::App for MainScreen {
 
             // Modal messages
             self.error_msg.show(ui.ctx());
+            if self.error_msg.confirmed() {
+                self.send(Command::RUNOFFLINE);
+            }
             self.settings_modal.show(ctx);
+            self.mods_panel.show(ctx);
             self.news.show_modal(ui);
 
             ui.ctx().request_repaint();
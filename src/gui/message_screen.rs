@@ -4,6 +4,8 @@ pub enum MsgLevel {
     Info,
     Warn,
     Error,
+    // Question with cancel and action buttons
+    Confirm,
 }
 
 #[derive(Default)]
@@ -12,6 +14,9 @@ pub struct MsgBoxScreen {
     msg: String,
     msg_level: MsgLevel,
     visible: bool,
+    action: String,
+    cancel: String,
+    confirmed: bool,
 }
 
 impl MsgBoxScreen {
@@ -21,6 +26,7 @@ impl MsgBoxScreen {
             msg: msg.into(),
             msg_level,
             visible: true,
+            ..Default::default()
         }
     }
 
@@ -30,6 +36,7 @@ impl MsgBoxScreen {
             msg: msg.into(),
             msg_level: MsgLevel::Info,
             visible: true,
+            ..Default::default()
         }
     }
 
@@ -39,6 +46,7 @@ impl MsgBoxScreen {
             msg: msg.into(),
             msg_level: MsgLevel::Warn,
             visible: true,
+            ..Default::default()
         }
     }
 
@@ -48,9 +56,32 @@ impl MsgBoxScreen {
             msg: msg.into(),
             msg_level: MsgLevel::Error,
             visible: true,
+            ..Default::default()
         }
     }
 
+    pub fn confirm(
+        title: impl Into<String>,
+        msg: impl Into<String>,
+        action: impl Into<String>,
+        cancel: impl Into<String>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            msg: msg.into(),
+            msg_level: MsgLevel::Confirm,
+            visible: true,
+            action: action.into(),
+            cancel: cancel.into(),
+            confirmed: false,
+        }
+    }
+
+    // Whether action button was pressed. Resets on read.
+    pub fn confirmed(&mut self) -> bool {
+        std::mem::take(&mut self.confirmed)
+    }
+
     pub fn show(&mut self, ctx: &egui::Context) {
         let mut visible = self.visible;
         egui::Window::new(&self.title)
@@ -85,6 +116,17 @@ impl MsgBoxScreen {
                                 }
                             });
                         }
+                        MsgLevel::Confirm => {
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                                if ui.button(&self.action).clicked() {
+                                    self.confirmed = true;
+                                    self.visible = false;
+                                }
+                                if ui.button(&self.cancel).clicked() {
+                                    self.visible = false;
+                                }
+                            });
+                        }
                     }
                 });
 
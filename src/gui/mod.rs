@@ -11,11 +11,15 @@ use crate::utils::constants::CAPTION;
 
 use self::main_screen::MainScreen;
 
+pub mod format;
 pub mod login_screen;
 pub mod main_screen;
 pub mod message_screen;
+pub mod mods_panel;
 pub mod news_widget;
 pub mod settings_modal;
+#[cfg(test)]
+mod tests;
 pub mod update_screen;
 pub mod window_frame;
 
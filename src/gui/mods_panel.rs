@@ -0,0 +1,298 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use egui::{Align, Color32, Context, Layout, RichText, TopBottomPanel, Ui, Vec2};
+use log::error;
+use serde_json::Value;
+
+use crate::minecraft::{
+    instance::Instance,
+    mod_manager::{self, InstalledMod, MOD_MANAGER},
+    mods::{ModManifest, MODS},
+};
+
+#[derive(PartialEq)]
+enum Tab {
+    Optional,
+    Installed,
+}
+
+pub struct ModsPanel {
+    pub is_open: bool,
+    locale: Value,
+    tab: Tab,
+    manifest: Option<ModManifest>,
+    // Manifest is requested from logic thread and not received yet
+    loading: bool,
+    choices: HashMap<String, bool>,
+    installed: Vec<InstalledMod>,
+}
+
+impl ModsPanel {
+    pub fn new(locale: Value) -> Self {
+        Self {
+            is_open: false,
+            locale,
+            tab: Tab::Optional,
+            manifest: None,
+            loading: false,
+            choices: HashMap::new(),
+            installed: Vec::new(),
+        }
+    }
+
+    // Manifest is loaded by logic thread after "MODS" command and passed to set_manifest
+    pub fn open(&mut self) {
+        self.manifest = None;
+        self.loading = true;
+        self.choices = Instance::load()
+            .map(|i| i.optional_mods)
+            .unwrap_or_else(|e| {
+                error!(target: MODS, "Couldn't load instance config. Error: {e}");
+                HashMap::new()
+            });
+        self.installed = Self::load_installed();
+        self.is_open = true;
+    }
+
+    pub fn set_manifest(&mut self, manifest: Option<ModManifest>) {
+        self.manifest = manifest;
+        self.loading = false;
+    }
+
+    fn load_installed() -> Vec<InstalledMod> {
+        let installed =
+            Instance::load().and_then(|i| mod_manager::list(&mod_manager::mods_dir()?, &i));
+
+        installed.unwrap_or_else(|e| {
+            error!(target: MOD_MANAGER, "Couldn't list installed mods. Error: {e}");
+            Vec::new()
+        })
+    }
+
+    fn show_error(e: Box<dyn std::error::Error>) {
+        error!(target: MOD_MANAGER, "{e}");
+        msgbox::create("Error", &e.to_string(), msgbox::IconType::Error).unwrap_or_else(|e| {
+            error!(target: MOD_MANAGER, "Couldn't show msgbox: {e}");
+        });
+    }
+
+    fn save(choices: &HashMap<String, bool>) {
+        let mut instance = match Instance::load() {
+            Ok(i) => i,
+            Err(e) => {
+                error!(target: MODS, "Couldn't load instance config. Error: {e}");
+                return;
+            }
+        };
+
+        instance.optional_mods = choices.clone();
+        if let Err(e) = instance.save() {
+            Self::show_error(e);
+        }
+    }
+
+    fn add_mods(files: Vec<PathBuf>) {
+        let result = Instance::load().and_then(|mut instance| {
+            let mods_dir = mod_manager::mods_dir()?;
+            for file in files {
+                mod_manager::add(&mods_dir, &file, &mut instance)?;
+            }
+            instance.save()
+        });
+
+        if let Err(e) = result {
+            Self::show_error(e);
+        }
+    }
+
+    fn draw_optional(
+        ui: &mut Ui,
+        locale: &Value,
+        manifest: &Option<ModManifest>,
+        loading: bool,
+        choices: &mut HashMap<String, bool>,
+    ) {
+        let manifest = match manifest {
+            Some(m) => m,
+            None if loading => {
+                ui.label(locale["mods_loading"].as_str().unwrap());
+                return;
+            }
+            None => {
+                ui.label(locale["mods_load_error"].as_str().unwrap());
+                return;
+            }
+        };
+
+        let conflicts = manifest.conflicts(choices);
+        for (a, b) in &conflicts {
+            ui.label(
+                RichText::new(format!(
+                    "\"{a}\" {} \"{b}\"",
+                    locale["mods_conflict"].as_str().unwrap()
+                ))
+                .color(Color32::RED),
+            );
+        }
+        if !conflicts.is_empty() {
+            ui.separator();
+        }
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                let optional: Vec<_> = manifest.mods.iter().filter(|m| !m.required).collect();
+
+                if optional.is_empty() {
+                    ui.label(locale["mods_no_optional"].as_str().unwrap());
+                }
+
+                for entry in optional {
+                    let mut checked = entry.is_selected(choices);
+                    if ui.checkbox(&mut checked, entry.display_name()).changed() {
+                        choices.insert(entry.filename.clone(), checked);
+                    }
+                    if !entry.description.is_empty() {
+                        ui.label(RichText::new(&entry.description).weak());
+                    }
+                    ui.separator();
+                }
+            });
+    }
+
+    // Returns true if mods dir was changed
+    fn draw_installed(ui: &mut Ui, locale: &Value, installed: &[InstalledMod]) -> bool {
+        let mut changed = false;
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                if installed.is_empty() {
+                    ui.label(locale["mods_no_installed"].as_str().unwrap());
+                }
+
+                for m in installed {
+                    ui.horizontal(|ui| {
+                        let mut enabled = m.enabled;
+                        if ui.checkbox(&mut enabled, m.display_name()).changed() {
+                            let result = mod_manager::mods_dir()
+                                .and_then(|d| mod_manager::set_enabled(&d, &m.filename, enabled));
+                            if let Err(e) = result {
+                                Self::show_error(e);
+                            }
+                            changed = true;
+                        }
+                        if m.user_added {
+                            ui.label(
+                                RichText::new(locale["mods_user_added"].as_str().unwrap()).weak(),
+                            );
+                        }
+                    });
+                    ui.separator();
+                }
+            });
+
+        changed
+    }
+
+    pub fn show(&mut self, ctx: &Context) {
+        let screen_size = ctx.screen_rect().size();
+        let mut is_open = self.is_open;
+        let mut refresh = false;
+
+        if self.is_open && self.tab == Tab::Installed {
+            let dropped: Vec<PathBuf> = ctx.input(|i| {
+                i.raw
+                    .dropped_files
+                    .iter()
+                    .filter_map(|f| f.path.clone())
+                    .collect()
+            });
+            if !dropped.is_empty() {
+                Self::add_mods(dropped);
+                refresh = true;
+            }
+        }
+
+        egui::Window::new(self.locale["mods_title"].as_str().unwrap())
+            .open(&mut self.is_open)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .fixed_size([screen_size.x / 1.5, screen_size.y / 2.])
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                TopBottomPanel::top("mods_top").show_inside(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(
+                            &mut self.tab,
+                            Tab::Optional,
+                            self.locale["mods_tab_optional"].as_str().unwrap(),
+                        );
+                        ui.selectable_value(
+                            &mut self.tab,
+                            Tab::Installed,
+                            self.locale["mods_tab_installed"].as_str().unwrap(),
+                        );
+                    });
+                });
+
+                TopBottomPanel::bottom("mods_bottom").show_inside(ui, |ui| {
+                    if self.tab == Tab::Installed {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button(self.locale["mods_add_btn"].as_str().unwrap())
+                                .clicked()
+                            {
+                                let files = rfd::FileDialog::new()
+                                    .add_filter("Minecraft mod", &["jar"])
+                                    .pick_files();
+                                if let Some(files) = files {
+                                    Self::add_mods(files);
+                                    refresh = true;
+                                }
+                            }
+                            ui.label(
+                                RichText::new(self.locale["mods_drop_hint"].as_str().unwrap())
+                                    .weak(),
+                            );
+                        });
+                        return;
+                    }
+
+                    ui.with_layout(Layout::right_to_left(Align::Max), |ui| {
+                        if ui
+                            .add_enabled(
+                                self.manifest.is_some(),
+                                egui::Button::new(self.locale["mods_save"].as_str().unwrap()),
+                            )
+                            .clicked()
+                        {
+                            Self::save(&self.choices);
+                            is_open = false;
+                        }
+                    });
+                });
+
+                match self.tab {
+                    Tab::Optional => Self::draw_optional(
+                        ui,
+                        &self.locale,
+                        &self.manifest,
+                        self.loading,
+                        &mut self.choices,
+                    ),
+                    Tab::Installed => {
+                        refresh |= Self::draw_installed(ui, &self.locale, &self.installed)
+                    }
+                }
+            });
+
+        if refresh {
+            self.installed = Self::load_installed();
+        }
+
+        if self.is_open {
+            self.is_open = is_open;
+        }
+    }
+}
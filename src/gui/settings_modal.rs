@@ -1,20 +1,85 @@
+use std::{path::PathBuf, sync::mpsc::Sender};
+
 use egui::{Align, Context, Layout, TopBottomPanel, Vec2};
+use log::error;
 use serde_json::Value;
 
-use crate::launcher::{config::AppConfig, locale::Locale};
+use crate::{
+    launcher::{
+        commands::Command,
+        config::{AppConfig, ProxyConfig, ProxyKind, ProxyMode},
+        locale::Locale,
+    },
+    minecraft::{
+        instance::{Instance, INSTANCE},
+        modpack::{default_folders, exportable_folders, ExportFormat, ExportOptions, MODPACK},
+    },
+    utils::cache::{cache_size, CACHE},
+};
+
+use super::format::format_bytes;
 
 pub struct SettingsModal {
     pub is_open: bool,
     locale: Value,
     curr_lang: String,
+    proxy: ProxyConfig,
+    authlib_injector: bool,
+    logic_sender: Sender<Command>,
+    modpack_path: String,
+    export_format: ExportFormat,
+    // Top level entries of game dir and whether they are exported
+    export_folders: Option<Vec<(String, bool)>>,
+    export_path: String,
+    // Computed when modal is opened
+    cache_size: Option<u64>,
 }
 
 impl SettingsModal {
-    pub fn new(locale: Value) -> Self {
+    pub fn new(locale: Value, logic_sender: Sender<Command>) -> Self {
         Self {
             is_open: false,
             curr_lang: locale["name"].as_str().unwrap().to_string(),
+            proxy: AppConfig::get_config().map(|c| c.proxy).unwrap_or_default(),
+            authlib_injector: Instance::load()
+                .map(|i| i.authlib_injector)
+                .unwrap_or_default(),
             locale,
+            logic_sender,
+            modpack_path: String::new(),
+            export_format: ExportFormat::Mrpack,
+            export_folders: None,
+            export_path: String::new(),
+            cache_size: None,
+        }
+    }
+
+    fn load_export_folders(format: ExportFormat) -> Vec<(String, bool)> {
+        let folders = Instance::game_dir()
+            .and_then(|d| exportable_folders(&d))
+            .unwrap_or_else(|e| {
+                error!(target: MODPACK, "Couldn't list game dir. Error: {e}");
+                Vec::new()
+            });
+        let selected = default_folders(format, &folders);
+
+        folders
+            .into_iter()
+            .map(|f| {
+                let checked = selected.contains(&f);
+                (f, checked)
+            })
+            .collect()
+    }
+
+    fn save_authlib_injector(enabled: bool) {
+        let result = Instance::load().and_then(|mut instance| {
+            instance.authlib_injector = enabled;
+            instance.save()
+        });
+
+        if let Err(e) = result {
+            error!(target: INSTANCE, "Couldn't save instance config. Error: {e}");
         }
     }
 
@@ -23,6 +88,10 @@ impl SettingsModal {
         let screen_size = ctx.screen_rect().size();
         let mut is_open = self.is_open;
 
+        if !self.is_open {
+            self.cache_size = None;
+        }
+
         egui::Window::new(self.locale["settings_title"].as_str().unwrap())
             .open(&mut self.is_open)
             .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
@@ -41,6 +110,181 @@ impl SettingsModal {
                                 }
                             });
                     });
+                    ui.checkbox(
+                        &mut self.authlib_injector,
+                        self.locale["settings_authlib"].as_str().unwrap(),
+                    );
+                    egui::CollapsingHeader::new(self.locale["settings_proxy"].as_str().unwrap())
+                        .show(ui, |ui| {
+                            let proxy = &mut self.proxy;
+                            ui.horizontal(|ui| {
+                                ui.radio_value(
+                                    &mut proxy.mode,
+                                    ProxyMode::None,
+                                    self.locale["settings_proxy_none"].as_str().unwrap(),
+                                );
+                                ui.radio_value(
+                                    &mut proxy.mode,
+                                    ProxyMode::System,
+                                    self.locale["settings_proxy_system"].as_str().unwrap(),
+                                );
+                                ui.radio_value(
+                                    &mut proxy.mode,
+                                    ProxyMode::Manual,
+                                    self.locale["settings_proxy_manual"].as_str().unwrap(),
+                                );
+                            });
+
+                            if proxy.mode != ProxyMode::Manual {
+                                return;
+                            }
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut proxy.kind, ProxyKind::Http, "HTTP");
+                                ui.radio_value(&mut proxy.kind, ProxyKind::Socks5, "SOCKS5");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label(self.locale["settings_proxy_host"].as_str().unwrap());
+                                ui.text_edit_singleline(&mut proxy.host);
+                                ui.label(self.locale["settings_proxy_port"].as_str().unwrap());
+                                ui.add(egui::DragValue::new(&mut proxy.port));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label(self.locale["settings_proxy_username"].as_str().unwrap());
+                                ui.text_edit_singleline(&mut proxy.username);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label(self.locale["settings_proxy_password"].as_str().unwrap());
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut proxy.password).password(true),
+                                );
+                            });
+                        });
+                    ui.horizontal(|ui| {
+                        ui.label(self.locale["settings_import"].as_str().unwrap());
+                        ui.text_edit_singleline(&mut self.modpack_path);
+                        if ui
+                            .add_enabled(
+                                !self.modpack_path.is_empty(),
+                                egui::Button::new(
+                                    self.locale["settings_import_btn"].as_str().unwrap(),
+                                ),
+                            )
+                            .clicked()
+                        {
+                            let path = PathBuf::from(self.modpack_path.trim());
+                            if self.logic_sender.send(Command::IMPORT(path)).is_err() {
+                                error!("Couldn't send \"IMPORT\" command.");
+                            }
+                            self.modpack_path.clear();
+                            is_open = false;
+                        }
+                    });
+                    let export = egui::CollapsingHeader::new(
+                        self.locale["settings_export"].as_str().unwrap(),
+                    )
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(self.locale["settings_export_format"].as_str().unwrap());
+                            let mrpack = ui.radio_value(
+                                &mut self.export_format,
+                                ExportFormat::Mrpack,
+                                "Modrinth (.mrpack)",
+                            );
+                            let server = ui.radio_value(
+                                &mut self.export_format,
+                                ExportFormat::ServerPackage,
+                                self.locale["settings_export_server"].as_str().unwrap(),
+                            );
+                            if mrpack.changed() || server.changed() {
+                                self.export_folders = None;
+                            }
+                        });
+
+                        let format = self.export_format;
+                        let folders = self
+                            .export_folders
+                            .get_or_insert_with(|| Self::load_export_folders(format));
+
+                        ui.label(self.locale["settings_export_folders"].as_str().unwrap());
+                        egui::ScrollArea::vertical()
+                            .max_height(120.)
+                            .show(ui, |ui| {
+                                for (name, checked) in folders.iter_mut() {
+                                    ui.checkbox(checked, name.as_str());
+                                }
+                            });
+
+                        ui.horizontal(|ui| {
+                            ui.label(self.locale["settings_export_output"].as_str().unwrap());
+                            ui.text_edit_singleline(&mut self.export_path);
+                            if ui
+                                .add_enabled(
+                                    !self.export_path.trim().is_empty(),
+                                    egui::Button::new(
+                                        self.locale["settings_export_btn"].as_str().unwrap(),
+                                    ),
+                                )
+                                .clicked()
+                            {
+                                let options = ExportOptions {
+                                    format,
+                                    folders: folders
+                                        .iter()
+                                        .filter(|(_, checked)| *checked)
+                                        .map(|(name, _)| name.clone())
+                                        .collect(),
+                                    output: PathBuf::from(self.export_path.trim()),
+                                };
+                                if self.logic_sender.send(Command::EXPORT(options)).is_err() {
+                                    error!("Couldn't send \"EXPORT\" command.");
+                                }
+                                is_open = false;
+                            }
+                        });
+                    });
+                    // Game dir could be changed while section was closed
+                    if export.fully_closed() {
+                        self.export_folders = None;
+                    }
+                    ui.horizontal(|ui| {
+                        let size = *self.cache_size.get_or_insert_with(|| {
+                            cache_size().unwrap_or_else(|e| {
+                                error!(target: CACHE, "Couldn't get cache size. Error: {e}");
+                                0
+                            })
+                        });
+                        ui.label(format!(
+                            "{} {}",
+                            self.locale["settings_cache"].as_str().unwrap(),
+                            format_bytes(size, &self.locale)
+                        ));
+                        if ui
+                            .add_enabled(
+                                size > 0,
+                                egui::Button::new(
+                                    self.locale["settings_cache_clear"].as_str().unwrap(),
+                                ),
+                            )
+                            .clicked()
+                        {
+                            if self.logic_sender.send(Command::CLEARCACHE).is_err() {
+                                error!("Couldn't send \"CLEARCACHE\" command.");
+                            }
+                            is_open = false;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(self.locale["settings_repair"].as_str().unwrap());
+                        if ui
+                            .button(self.locale["settings_repair_btn"].as_str().unwrap())
+                            .clicked()
+                        {
+                            if self.logic_sender.send(Command::REPAIR).is_err() {
+                                error!("Couldn't send \"REPAIR\" command.");
+                            }
+                            is_open = false;
+                        }
+                    });
                     ui.with_layout(Layout::right_to_left(Align::Max), |ui| {
                         if ui
                             .button(self.locale["settings_save"].as_str().unwrap())
@@ -48,8 +292,10 @@ impl SettingsModal {
                         {
                             let config = AppConfig {
                                 locale: self.curr_lang.clone(),
+                                proxy: self.proxy.clone(),
                             };
                             config.save();
+                            Self::save_authlib_injector(self.authlib_injector);
                             is_open = false;
                         }
                     });
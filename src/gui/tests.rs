@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use crate::{
+    launcher::commands::{DownloadProgress, Phase},
+    utils::{disk::NotEnoughSpace, test_helpers::locale},
+};
+
+use super::format::{disk_space_text, format_bytes, format_eta, progress_text};
+
+#[test]
+fn format_test() {
+    let en = locale("en-US");
+    let ru = locale("ru-RU");
+
+    assert_eq!(format_bytes(512, &en), "512 B");
+    assert_eq!(format_bytes(1536, &en), "1.5 KB");
+    assert_eq!(format_bytes(1536, &ru), "1,5 КБ");
+    assert_eq!(format_bytes(3 * 1024 * 1024 * 1024, &en), "3.0 GB");
+    assert_eq!(format_eta(Duration::from_secs(65)), "1:05");
+    assert_eq!(format_eta(Duration::from_secs(3725)), "1:02:05");
+
+    let progress = DownloadProgress {
+        phase: Phase::Mods,
+        files: 3,
+        total_files: 10,
+        bytes: 10 * 1024 * 1024,
+        total_bytes: 40 * 1024 * 1024,
+        speed: 2. * 1024. * 1024.,
+        ..Default::default()
+    };
+    assert_eq!(
+        progress_text(&progress, &en),
+        "3/10 files, 10.0 MB / 40.0 MB, 2.0 MB/s, 0:15 left"
+    );
+
+    let text = disk_space_text(
+        &NotEnoughSpace {
+            required: 3 * 1024 * 1024 * 1024,
+            available: 1024 * 1024 * 1024,
+        },
+        &en,
+    );
+    assert!(text.starts_with("Not enough disk space: 3.0 GB required, 1.0 GB available."));
+}
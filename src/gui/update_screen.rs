@@ -8,11 +8,17 @@ use log::{debug, error};
 use serde_json::Value;
 
 use crate::{
-    launcher::launcher_update::{download_launcher, need_update, Command, UpdateData},
+    launcher::{
+        commands::DownloadProgress,
+        launcher_update::{download_launcher, need_update, Command},
+    },
     utils::constants::CAPTION,
 };
 
-use super::window_frame::{windowframe, WindowFrameData};
+use super::{
+    format::progress_text,
+    window_frame::{windowframe, WindowFrameData},
+};
 
 const UPDATE: &str = "UPDATESCREEN";
 
@@ -21,6 +27,8 @@ pub struct UpdateScreen {
     data_receiver: Option<Receiver<Command>>,
     wframe: WindowFrameData,
     locale: Value,
+    // Last received progress, None while checking for update
+    progress: Option<DownloadProgress>,
 }
 
 impl UpdateScreen {
@@ -33,6 +41,7 @@ impl UpdateScreen {
                 .with_minimaizable(false)
                 .with_movable(false),
             locale,
+            progress: None,
         }
     }
 
@@ -109,44 +118,35 @@ impl UpdateScreen {
         Ok(())
     }
 
-    fn draw_contents(&self, ui: &mut Ui) {
-        let mut data = UpdateData {
-            downloaded: 0,
-            size: 100,
-        };
-
-        let mut is_checking = true;
-
+    fn draw_contents(&mut self, ui: &mut Ui) {
         if self.data_receiver.is_some() {
             let recv = self.data_receiver.as_ref().unwrap().try_recv();
             if recv.is_ok() {
                 match recv.unwrap() {
-                    Command::Data(data_) => {
-                        is_checking = false;
-                        data = data_
-                    }
+                    Command::Data(data) => self.progress = Some(data),
                     Command::Completed => ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close),
                     Command::Abort => ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close),
                 }
             }
         }
 
-        CentralPanel::default().show_inside(ui, |ui| {
-            if is_checking {
+        CentralPanel::default().show_inside(ui, |ui| match &self.progress {
+            None => {
                 ui.with_layout(
                     egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
                     |ui| {
                         ui.label(self.locale["update_label_check"].as_str().unwrap());
                     },
                 );
-            } else {
+            }
+            Some(data) => {
                 ui.label(self.locale["update_label_update"].as_str().unwrap());
-                let progress =
-                    ProgressBar::new(data.downloaded as f32 / data.size as f32).text(format!(
-                        "{}Mb/{}Mb",
-                        data.downloaded / (1024 * 2),
-                        data.size / (1024 * 2)
-                    ));
+                let fraction = if data.total_bytes > 0 {
+                    data.bytes as f32 / data.total_bytes as f32
+                } else {
+                    0.0
+                };
+                let progress = ProgressBar::new(fraction).text(progress_text(data, &self.locale));
                 ui.add(progress);
             }
         });
@@ -157,6 +157,7 @@ impl UpdateScreen {
 
 impl eframe::App for UpdateScreen {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        windowframe::show(&mut self.wframe.clone(), ctx, |ui| self.draw_contents(ui));
+        let mut wframe = self.wframe.clone();
+        windowframe::show(&mut wframe, ctx, |ui| self.draw_contents(ui));
     }
 }
@@ -1,12 +1,62 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::minecraft::{modpack::ExportOptions, mods::ModManifest};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Phase {
+    #[default]
+    Game,
+    Mods,
+    Modpack,
+    Launcher,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownloadProgress {
+    pub phase: Phase,
+    // File being downloaded, empty if unknown
+    pub file: String,
+    // Zero total files means single archive download
+    pub files: u64,
+    pub total_files: u64,
+    pub bytes: u64,
+    pub total_bytes: u64,
+    // Bytes per second, moving average
+    pub speed: f64,
+}
+
+impl DownloadProgress {
+    pub fn eta(&self) -> Option<Duration> {
+        if self.speed <= 0. || self.total_bytes == 0 {
+            return None;
+        }
+
+        let left = self.total_bytes.saturating_sub(self.bytes) as f64;
+        Some(Duration::from_secs_f64(left / self.speed))
+    }
+}
+
 #[derive(PartialEq)]
 pub enum Command {
-    RUN,      // Launch minecraft
-    CONTINUE, // Shutdown game and launch launcher
+    RUN,        // Launch minecraft
+    RUNOFFLINE, // Launch last validated minecraft without update server
+    CONTINUE,   // Shutdown game and launch launcher
     VALIDATE,
-    DOWNLOAD((u64, u64)),
-    UNZIPING,
+    DOWNLOAD(DownloadProgress),
+    UNZIPING(DownloadProgress),
     PLAY,
     ERROR(String),
-    NONE, // Nothing
-    EXIT, // Exit from launcher
+    INFO(String),
+    OFFLINE(String),       // Update server is unreachable, offer offline launch
+    IMPORT(PathBuf),       // Import modpack into current instance
+    EXPORT(ExportOptions), // Export current instance
+    CANCEL,                // Stop download and remove partial files
+    PAUSE,                 // Pause download
+    RESUME,                // Continue paused download
+    REPAIR,                // Check all game files and fix broken ones
+    CLEARCACHE,            // Remove cached downloads
+    MODS,                  // Load mod manifest for mods panel
+    MODMANIFEST(Option<ModManifest>), // Loaded mod manifest, None if it couldn't be loaded
+    NONE,                  // Nothing
+    EXIT,                  // Exit from launcher
 }
@@ -1,17 +1,97 @@
 use std::{
+    error::Error,
     fs::{self, File},
     io::{Read, Write},
 };
 
 use log::{error, warn};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use sys_locale::get_locale;
 
 use crate::utils::constants::LAUNCHER_DIR;
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyMode {
+    // Direct connection, environment variables are ignored
+    None,
+    // HTTP_PROXY, HTTPS_PROXY and ALL_PROXY environment variables, as before proxy settings
+    #[default]
+    System,
+    Manual,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyKind {
+    #[default]
+    Http,
+    Socks5,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub mode: ProxyMode,
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    // Empty if proxy doesn't need authentication
+    pub username: String,
+    pub password: String,
+}
+
+impl ProxyConfig {
+    // Url of manual proxy with credentials
+    pub fn url(&self) -> Result<Option<Url>, Box<dyn Error>> {
+        if self.mode != ProxyMode::Manual {
+            return Ok(None);
+        }
+
+        let scheme = match self.kind {
+            ProxyKind::Http => "http",
+            // Host names are resolved by proxy
+            ProxyKind::Socks5 => "socks5h",
+        };
+        let mut url = Url::parse(&format!("{scheme}://{}:{}", self.host.trim(), self.port))?;
+        if !self.username.is_empty() {
+            url.set_username(&self.username)
+                .map_err(|_| "Couldn't set proxy username.")?;
+            url.set_password(Some(&self.password))
+                .map_err(|_| "Couldn't set proxy password.")?;
+        }
+
+        Ok(Some(url))
+    }
+
+    // Java system properties for the same proxy. Credentials are not passed, they would be
+    // visible to everyone in process list.
+    pub fn jvm_args(&self) -> Vec<String> {
+        let (host, port) = (self.host.trim(), self.port);
+
+        match (self.mode, self.kind) {
+            (ProxyMode::None, _) => Vec::new(),
+            (ProxyMode::System, _) => vec!["-Djava.net.useSystemProxies=true".to_string()],
+            (ProxyMode::Manual, ProxyKind::Http) => vec![
+                format!("-Dhttp.proxyHost={host}"),
+                format!("-Dhttp.proxyPort={port}"),
+                format!("-Dhttps.proxyHost={host}"),
+                format!("-Dhttps.proxyPort={port}"),
+            ],
+            (ProxyMode::Manual, ProxyKind::Socks5) => vec![
+                format!("-DsocksProxyHost={host}"),
+                format!("-DsocksProxyPort={port}"),
+            ],
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct AppConfig {
     pub locale: String,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
 }
 
 impl Default for AppConfig {
@@ -21,7 +101,10 @@ impl Default for AppConfig {
             String::from("en-US")
         });
 
-        Self { locale }
+        Self {
+            locale,
+            proxy: ProxyConfig::default(),
+        }
     }
 }
 
@@ -1,13 +1,26 @@
 use egui::TextBuffer;
-use log::{debug, error};
+use log::{debug, error, info, warn};
 use serde_json::Value;
 
 use crate::api::account::Account;
 use crate::api::auth::Auth;
+use crate::api::session::Session;
+use crate::gui::format::{disk_space_text, format_bytes};
 use crate::launcher::commands::Command;
 use crate::minecraft;
-use crate::minecraft::downloader::{self, download_minecraft};
-use crate::minecraft::validate::{self, is_valid_files};
+use crate::minecraft::authlib::{self, AuthlibInjector};
+use crate::minecraft::instance::{self, Instance};
+use crate::minecraft::mod_inspector::{self, check_dependencies, inspect_dir};
+use crate::minecraft::modpack::{self, export, import};
+use crate::minecraft::mods::{self, sync_mods, ModManifest};
+use crate::minecraft::offline::{self, offline_available, LastValidation};
+use crate::minecraft::repair::{self, repair_game};
+use crate::minecraft::updater::{self, update_game};
+use crate::minecraft::validate::{self, validate_files};
+use crate::utils::cache::{self, clear_cache};
+use crate::utils::disk::NotEnoughSpace;
+use crate::utils::download::{is_cancelled, CancelToken};
+use crate::utils::hash_cache::HashCache;
 use crate::utils::relaunch::relaunch;
 use crate::{gui::GUI, minecraft::Minecraft};
 use std::process::{exit, ExitStatus};
@@ -28,7 +41,9 @@ impl LauncherController {
     }
 
     pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
-        if let Err(e) = self.account.send_online() {
+        if self.account.is_offline() {
+            info!(target: CONTROLLER, "Server is unreachable, working offline.");
+        } else if let Err(e) = self.account.send_online() {
             error!("Couldn't send online status. Error: {e}");
             Auth::remove_token().unwrap_or_else(|e| {
                 error!("Couldn't remove token. Error: {e}");
@@ -52,47 +67,198 @@ impl LauncherController {
 
         let launcher_sender_thread = launcher_sender.clone();
 
+        let account_thread = self.account.clone();
+        let locale_thread = self.locale.clone();
+
+        // Token of current download, replaced on every run
+        let download_control = Arc::new(Mutex::new(CancelToken::default()));
+
         let logic_thread = std::thread::spawn(move || loop {
             match logic_receiver.recv().unwrap() {
-                Command::RUN => {
+                command @ (Command::RUN | Command::RUNOFFLINE) => {
                     debug!(target: CONTROLLER, "RUN command.");
 
+                    // Update server is skipped, last validated files are launched
+                    let offline = command == Command::RUNOFFLINE;
+
                     let mut in_game_guard = in_game_thread.lock().unwrap();
                     *in_game_guard = true;
 
                     let logic_sender = logic_sender_thread.clone();
                     let launcher_sender = launcher_sender_thread.clone();
+                    let account = account_thread.clone();
+                    let locale = locale_thread.clone();
+
+                    let cancel = CancelToken::default();
+                    *download_control.lock().unwrap() = cancel.clone();
 
                     std::thread::spawn(move || {
                         launcher_sender.send(Command::VALIDATE).unwrap();
                         'run: {
-                            let is_valid = match is_valid_files() {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    error!(target: validate::VALIDATOR, "{e}");
-                                    launcher_sender.send(Command::ERROR("Couldn't connect to update server. Check your internet connection.".to_string()))
-                                        .unwrap_or_else(|_| {
+                            if offline {
+                                let changed = match (LastValidation::load(), Instance::game_dir()) {
+                                    (Some(v), Ok(d)) => {
+                                        let mut cache = HashCache::load();
+                                        let changed = v.check(&d, &mut cache);
+                                        cache.save().unwrap_or_else(|e| {
+                                            warn!(target: offline::OFFLINE, "Couldn't save hash cache. Error: {e}");
+                                        });
+                                        changed
+                                    }
+                                    (None, _) => Err("Game was never validated.".into()),
+                                    (_, Err(e)) => Err(e),
+                                };
+                                match changed {
+                                    Ok(c) if c.is_empty() => (),
+                                    Ok(c) => {
+                                        launcher_sender.send(Command::ERROR(format!("Game files were changed, connect to the internet to repair them:\n{}", c.join("\n")))).unwrap_or_else(|_| {
                                             error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
-                                            panic!();
                                         });
-                                    break 'run;
+                                        break 'run;
+                                    }
+                                    Err(e) => {
+                                        error!(target: offline::OFFLINE, "Error while checking game files. Error: {e}");
+                                        launcher_sender.send(Command::ERROR(format!("Couldn't check game files: {e}"))).unwrap_or_else(|_| {
+                                            error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                        });
+                                        break 'run;
+                                    }
                                 }
-                            };
-                            if !is_valid {
-                                match download_minecraft(launcher_sender.clone()) {
-                                    Ok(_) => (),
+                            } else {
+                                let (manifest, diff) = match validate_files(false) {
+                                    Ok(v) => v,
                                     Err(e) => {
-                                        error!(target: downloader::DOWNLOAD, "Error while downloading minecraft. Error: {e}");
-                                        launcher_sender.send(Command::ERROR(format!("Error while connecting to update server: {e}"))).unwrap_or_else(|_| {
-                                        error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
-                                        panic!();
-                                    });
+                                        error!(target: validate::VALIDATOR, "{e}");
+                                        let message = "Couldn't connect to update server. Check your internet connection.".to_string();
+                                        let command = match offline_available() {
+                                            true => Command::OFFLINE(message),
+                                            false => Command::ERROR(message),
+                                        };
+                                        launcher_sender.send(command)
+                                            .unwrap_or_else(|_| {
+                                                error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                                panic!();
+                                            });
                                         break 'run;
                                     }
                                 };
+                                if !diff.is_valid() {
+                                    match update_game(
+                                        launcher_sender.clone(),
+                                        &manifest,
+                                        &diff,
+                                        &cancel,
+                                    ) {
+                                        Ok(_) => (),
+                                        Err(e) if is_cancelled(e.as_ref()) => {
+                                            info!(target: updater::UPDATER, "Game update cancelled.");
+                                            break 'run;
+                                        }
+                                        Err(e) => {
+                                            error!(target: updater::UPDATER, "Error while updating minecraft. Error: {e}");
+                                            let text = match e.downcast_ref::<NotEnoughSpace>() {
+                                                Some(space) => disk_space_text(space, &locale),
+                                                None => format!(
+                                                    "Error while connecting to update server: {e}"
+                                                ),
+                                            };
+                                            launcher_sender.send(Command::ERROR(text)).unwrap_or_else(|_| {
+                                            error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                            panic!();
+                                        });
+                                            break 'run;
+                                        }
+                                    };
+                                }
+
+                                if let Err(e) = sync_mods(launcher_sender.clone(), &cancel) {
+                                    if is_cancelled(e.as_ref()) {
+                                        info!(target: mods::MODS, "Mods synchronization cancelled.");
+                                        break 'run;
+                                    }
+                                    error!(target: mods::MODS, "Error while synchronizing mods. Error: {e}");
+                                    let text = match e.downcast_ref::<NotEnoughSpace>() {
+                                        Some(space) => disk_space_text(space, &locale),
+                                        None => format!("Error while synchronizing mods: {e}"),
+                                    };
+                                    launcher_sender.send(Command::ERROR(text)).unwrap_or_else(|_| {
+                                        error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                        panic!();
+                                    });
+                                    break 'run;
+                                }
+
+                                if cancel.is_cancelled() {
+                                    info!(target: CONTROLLER, "Launch cancelled.");
+                                    break 'run;
+                                }
+
+                                match Instance::game_dir().and_then(|d| LastValidation::record(&manifest, &d)) {
+                                    Ok(v) => v.save().unwrap_or_else(|e| {
+                                        error!(target: offline::OFFLINE, "Couldn't save validation. Error: {e}");
+                                    }),
+                                    Err(e) => error!(target: offline::OFFLINE, "Couldn't record validation. Error: {e}"),
+                                }
+                            }
+
+                            let issues = match Instance::load().and_then(|i| {
+                                Ok((inspect_dir(&Instance::game_dir()?.join("mods"))?, i))
+                            }) {
+                                Ok((m, i)) => check_dependencies(&m, &i),
+                                Err(e) => {
+                                    error!(target: mod_inspector::INSPECTOR, "Error while inspecting mods. Error: {e}");
+                                    Vec::new()
+                                }
+                            };
+                            if !issues.is_empty() {
+                                let issues: Vec<String> =
+                                    issues.iter().map(|i| i.to_string()).collect();
+                                error!(target: mod_inspector::INSPECTOR, "Mod dependencies are not satisfied: {:?}", issues);
+                                launcher_sender.send(Command::ERROR(format!("Mod dependencies are not satisfied:\n{}", issues.join("\n")))).unwrap_or_else(|_| {
+                                    error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                    panic!();
+                                });
+                                break 'run;
                             }
 
-                            let minecraft = match Minecraft::new() {
+                            let session = match offline {
+                                true => {
+                                    Session::cached().ok_or_else(|| "No cached session.".into())
+                                }
+                                false => Session::new(&account),
+                            };
+                            let session = match session {
+                                Ok(s) => {
+                                    if !offline {
+                                        s.save().unwrap_or_else(|e| {
+                                            error!(target: CONTROLLER, "Couldn't cache game session. Error: {e}");
+                                        });
+                                    }
+                                    s
+                                }
+                                Err(e) => {
+                                    error!(target: CONTROLLER, "Error while requesting game session. Error: {e}");
+                                    launcher_sender.send(Command::ERROR(format!("Couldn't get game session: {e}"))).unwrap_or_else(|_| {
+                                    error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                    panic!();
+                                });
+                                    break 'run;
+                                }
+                            };
+
+                            let instance = match Instance::load() {
+                                Ok(i) => i,
+                                Err(e) => {
+                                    error!(target: instance::INSTANCE, "Error while loading instance config. Error: {e}");
+                                    launcher_sender.send(Command::ERROR(format!("Error while loading instance config: {e}"))).unwrap_or_else(|_| {
+                                    error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                    panic!();
+                                });
+                                    break 'run;
+                                }
+                            };
+
+                            let mut minecraft = match Minecraft::new(&session, &instance) {
                                 Ok(m) => m,
                                 Err(e) => {
                                     error!(target: minecraft::MINECRAFT, "Error while initializing minecraft. Error: {e}");
@@ -103,6 +269,25 @@ impl LauncherController {
                                     break 'run;
                                 }
                             };
+
+                            if instance.authlib_injector {
+                                let injector = match offline {
+                                    true => AuthlibInjector::cached(),
+                                    false => AuthlibInjector::prepare(),
+                                };
+                                match injector {
+                                    Ok(injector) => minecraft.set_authlib_injector(&injector),
+                                    Err(e) => {
+                                        error!(target: authlib::AUTHLIB, "Error while preparing authlib-injector. Error: {e}");
+                                        launcher_sender.send(Command::ERROR(format!("Error while preparing authlib-injector: {e}"))).unwrap_or_else(|_| {
+                                        error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                        panic!();
+                                    });
+                                        break 'run;
+                                    }
+                                }
+                            }
+
                             match minecraft.run() {
                             Ok(c) => c,
                             Err(e) => {
@@ -131,6 +316,176 @@ impl LauncherController {
                         });
                     });
                 }
+                Command::IMPORT(path) => {
+                    debug!(target: CONTROLLER, "IMPORT command.");
+
+                    let mut in_game_guard = in_game_thread.lock().unwrap();
+                    if *in_game_guard {
+                        launcher_sender_thread.send(Command::ERROR("Modpack couldn't be imported while game is updating or running.".to_string())).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                        });
+                        continue;
+                    }
+                    *in_game_guard = true;
+
+                    let logic_sender = logic_sender_thread.clone();
+                    let launcher_sender = launcher_sender_thread.clone();
+
+                    std::thread::spawn(move || {
+                        match import(&path, launcher_sender.clone()) {
+                            Ok(dir) => {
+                                launcher_sender.send(Command::INFO(format!("Modpack imported to {}", dir.display()))).unwrap_or_else(|_| {
+                                    error!(target: CONTROLLER, "Error while sending \"INFO\" command.");
+                                });
+                            }
+                            Err(e) => {
+                                error!(target: modpack::MODPACK, "Error while importing modpack. Error: {e}");
+                                launcher_sender.send(Command::ERROR(format!("Error while importing modpack: {e}"))).unwrap_or_else(|_| {
+                                    error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                });
+                            }
+                        }
+
+                        logic_sender.send(Command::CONTINUE).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"CONTINUE\" command.");
+                        });
+                        launcher_sender.send(Command::CONTINUE).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"CONTINUE\" command.");
+                        });
+                    });
+                }
+                Command::EXPORT(options) => {
+                    debug!(target: CONTROLLER, "EXPORT command.");
+
+                    let mut in_game_guard = in_game_thread.lock().unwrap();
+                    if *in_game_guard {
+                        launcher_sender_thread.send(Command::ERROR("Instance couldn't be exported while game is updating or running.".to_string())).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                        });
+                        continue;
+                    }
+                    *in_game_guard = true;
+
+                    let logic_sender = logic_sender_thread.clone();
+                    let launcher_sender = launcher_sender_thread.clone();
+
+                    std::thread::spawn(move || {
+                        match export(&options) {
+                            Ok(()) => {
+                                launcher_sender.send(Command::INFO(format!("Instance exported to {}", options.output.display()))).unwrap_or_else(|_| {
+                                    error!(target: CONTROLLER, "Error while sending \"INFO\" command.");
+                                });
+                            }
+                            Err(e) => {
+                                error!(target: modpack::MODPACK, "Error while exporting instance. Error: {e}");
+                                launcher_sender.send(Command::ERROR(format!("Error while exporting instance: {e}"))).unwrap_or_else(|_| {
+                                    error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                                });
+                            }
+                        }
+
+                        logic_sender.send(Command::CONTINUE).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"CONTINUE\" command.");
+                        });
+                        launcher_sender.send(Command::CONTINUE).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"CONTINUE\" command.");
+                        });
+                    });
+                }
+                Command::REPAIR => {
+                    debug!(target: CONTROLLER, "REPAIR command.");
+
+                    let mut in_game_guard = in_game_thread.lock().unwrap();
+                    if *in_game_guard {
+                        launcher_sender_thread.send(Command::ERROR("Game couldn't be repaired while it is updating or running.".to_string())).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"ERROR\" command.");
+                        });
+                        continue;
+                    }
+                    *in_game_guard = true;
+
+                    let logic_sender = logic_sender_thread.clone();
+                    let launcher_sender = launcher_sender_thread.clone();
+                    let locale = locale_thread.clone();
+
+                    let cancel = CancelToken::default();
+                    *download_control.lock().unwrap() = cancel.clone();
+
+                    std::thread::spawn(move || {
+                        launcher_sender.send(Command::VALIDATE).unwrap();
+                        let result =
+                            repair_game(launcher_sender.clone(), &cancel).and_then(|report| {
+                                sync_mods(launcher_sender.clone(), &cancel)?;
+                                Ok(report)
+                            });
+                        let command = match result {
+                            Ok(report) => Some(Command::INFO(report.summary())),
+                            Err(e) if is_cancelled(e.as_ref()) => {
+                                info!(target: repair::REPAIR, "Repair cancelled.");
+                                None
+                            }
+                            Err(e) => {
+                                error!(target: repair::REPAIR, "Error while repairing game. Error: {e}");
+                                Some(Command::ERROR(match e.downcast_ref::<NotEnoughSpace>() {
+                                    Some(space) => disk_space_text(space, &locale),
+                                    None => format!("Error while repairing game: {e}"),
+                                }))
+                            }
+                        };
+                        if let Some(command) = command {
+                            launcher_sender.send(command).unwrap_or_else(|_| {
+                                error!(target: CONTROLLER, "Error while sending \"INFO\" command.");
+                            });
+                        }
+
+                        logic_sender.send(Command::CONTINUE).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"CONTINUE\" command.");
+                        });
+                        launcher_sender.send(Command::CONTINUE).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"CONTINUE\" command.");
+                        });
+                    });
+                }
+                Command::CLEARCACHE => {
+                    debug!(target: CONTROLLER, "CLEARCACHE command.");
+
+                    // Cached files could be in use by running update
+                    let command = if *in_game_thread.lock().unwrap() {
+                        Command::ERROR(
+                            "Cache couldn't be cleared while game is updating or running."
+                                .to_string(),
+                        )
+                    } else {
+                        match clear_cache() {
+                            Ok(size) => Command::INFO(format!(
+                                "Cache cleared, {} freed.",
+                                format_bytes(size, &locale_thread)
+                            )),
+                            Err(e) => {
+                                error!(target: cache::CACHE, "Error while clearing cache. Error: {e}");
+                                Command::ERROR(format!("Error while clearing cache: {e}"))
+                            }
+                        }
+                    };
+                    launcher_sender.send(command).unwrap_or_else(|_| {
+                        error!(target: CONTROLLER, "Error while sending \"INFO\" command.");
+                    });
+                }
+                Command::MODS => {
+                    debug!(target: CONTROLLER, "MODS command.");
+
+                    let launcher_sender = launcher_sender_thread.clone();
+
+                    // Server request could take a while, don't block other commands
+                    std::thread::spawn(move || {
+                        let manifest = ModManifest::load()
+                            .map_err(|e| error!(target: mods::MODS, "Couldn't load mod manifest. Error: {e}"))
+                            .ok();
+                        launcher_sender.send(Command::MODMANIFEST(manifest)).unwrap_or_else(|_| {
+                            error!(target: CONTROLLER, "Error while sending \"MODMANIFEST\" command.");
+                        });
+                    });
+                }
                 Command::CONTINUE => {
                     debug!(target: CONTROLLER, "CONTINUE command.");
 
@@ -148,9 +503,28 @@ impl LauncherController {
 
                     *in_game_guard = false;
                 }
+                Command::CANCEL => {
+                    debug!(target: CONTROLLER, "CANCEL command.");
+
+                    download_control.lock().unwrap().cancel();
+                }
+                Command::PAUSE => {
+                    debug!(target: CONTROLLER, "PAUSE command.");
+
+                    download_control.lock().unwrap().pause();
+                }
+                Command::RESUME => {
+                    debug!(target: CONTROLLER, "RESUME command.");
+
+                    download_control.lock().unwrap().resume();
+                }
                 Command::EXIT => {
                     debug!(target: CONTROLLER, "EXIT command.");
 
+                    // Don't leave download thread writing after window is closed. Partial download
+                    // is kept, so it's resumed on next launch.
+                    download_control.lock().unwrap().abort();
+
                     break;
                 }
                 _ => (),
@@ -176,18 +550,20 @@ impl LauncherController {
 
         logic_thread.join().unwrap();
 
-        if let Err(e) = self.account.send_offline() {
-            error!("Couldn't send online status. Error: {e}");
-            Auth::remove_token().unwrap_or_else(|e| {
-                error!("Couldn't remove token. Error: {e}");
-            });
+        if !self.account.is_offline() {
+            if let Err(e) = self.account.send_offline() {
+                error!("Couldn't send online status. Error: {e}");
+                Auth::remove_token().unwrap_or_else(|e| {
+                    error!("Couldn't remove token. Error: {e}");
+                });
 
-            relaunch().unwrap_or_else(|e| {
-                error!("Couldn't relaunch launcher. Error: {e}");
-                exit(-1);
-            });
+                relaunch().unwrap_or_else(|e| {
+                    error!("Couldn't relaunch launcher. Error: {e}");
+                    exit(-1);
+                });
 
-            return Ok(());
+                return Ok(());
+            }
         }
 
         Ok(())
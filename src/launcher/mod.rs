@@ -3,3 +3,5 @@ pub mod config;
 pub mod launcher_controller;
 pub mod launcher_update;
 pub mod locale;
+#[cfg(test)]
+mod tests;
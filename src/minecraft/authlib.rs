@@ -0,0 +1,174 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+
+use crate::utils::{
+    constants::{AUTHLIB_INJECTOR_META, LAUNCHER_DIR, URL, YGGDRASIL_URL},
+    hash::sha256_file,
+    http::client_builder,
+};
+
+pub const AUTHLIB: &str = "MINECRAFT/AUTHLIB";
+
+#[derive(Deserialize)]
+struct Checksums {
+    sha256: String,
+}
+
+#[derive(Deserialize)]
+struct ArtifactInfo {
+    version: String,
+    download_url: String,
+    checksums: Checksums,
+}
+
+// authlib-injector java agent. Makes the client trust our Yggdrasil session server.
+pub struct AuthlibInjector {
+    jar: PathBuf,
+    yggdrasil: String,
+    prefetched: String,
+}
+
+impl AuthlibInjector {
+    fn jar_path() -> Result<PathBuf, Box<dyn Error>> {
+        Ok(dirs::data_dir()
+            .ok_or("OS data dir not found.")?
+            .join(LAUNCHER_DIR)
+            .join("authlib-injector.jar"))
+    }
+
+    pub fn prepare() -> Result<Self, Box<dyn Error>> {
+        Self::prepare_at(
+            &Self::jar_path()?,
+            AUTHLIB_INJECTOR_META,
+            &Self::yggdrasil_url(),
+        )
+    }
+
+    // Update agent and metadata. If authlib-injector or our server is unreachable, agent and
+    // metadata saved by last successful run are used.
+    pub fn prepare_at(jar: &Path, meta_url: &str, yggdrasil: &str) -> Result<Self, Box<dyn Error>> {
+        match Self::fetch(jar, meta_url, yggdrasil) {
+            Ok(injector) => Ok(injector),
+            Err(e) => {
+                warn!(target: AUTHLIB, "Couldn't update authlib-injector, trying cached one. Error: {e}");
+                Self::load(jar, yggdrasil).map_err(|_| e)
+            }
+        }
+    }
+
+    fn fetch(jar: &Path, meta_url: &str, yggdrasil: &str) -> Result<Self, Box<dyn Error>> {
+        let client = client_builder()?.timeout(Duration::from_secs(10)).build()?;
+
+        let resp = match client.get(meta_url).send() {
+            Ok(r) => Ok(r),
+            Err(e) => {
+                error!(target: AUTHLIB, "Error while requesting authlib-injector info. Error: {e}");
+                Err(e)
+            }
+        }?;
+
+        if !resp.status().is_success() {
+            return Err(format!("Server error: {}", resp.status()).into());
+        }
+
+        let info = serde_json::from_str::<ArtifactInfo>(&resp.text()?)?;
+
+        if jar.exists() && sha256_file(jar)? == info.checksums.sha256 {
+            debug!(target: AUTHLIB, "authlib-injector {} is up to date.", info.version);
+        } else {
+            info!(target: AUTHLIB, "Downloading authlib-injector {}...", info.version);
+
+            let tmp = jar.with_extension("jar.tmp");
+            let mut resp = client.get(&info.download_url).send()?;
+            if !resp.status().is_success() {
+                return Err(format!("Server error: {}", resp.status()).into());
+            }
+
+            let mut file = File::create(&tmp)?;
+            std::io::copy(&mut resp, &mut file)?;
+            drop(file);
+
+            if let Err(e) = check_jar(&tmp, &info.checksums.sha256) {
+                fs::remove_file(&tmp)?;
+                return Err(e);
+            }
+
+            fs::rename(&tmp, jar)?;
+            info!(target: AUTHLIB, "authlib-injector downloaded.");
+        }
+
+        // Prefetched metadata saves the agent a request on game startup
+        let resp = match client.get(yggdrasil).send() {
+            Ok(r) => Ok(r),
+            Err(e) => {
+                error!(target: AUTHLIB, "Error while requesting yggdrasil metadata. Error: {e}");
+                Err(e)
+            }
+        }?;
+
+        if !resp.status().is_success() {
+            return Err(format!("Server error: {}", resp.status()).into());
+        }
+
+        let prefetched = STANDARD.encode(resp.bytes()?);
+        fs::write(jar.with_file_name("yggdrasil.prefetched"), &prefetched)?;
+
+        Ok(Self {
+            jar: jar.to_path_buf(),
+            yggdrasil: yggdrasil.to_string(),
+            prefetched,
+        })
+    }
+
+    // Agent and metadata saved by last `prepare`, for launch without network
+    pub fn cached() -> Result<Self, Box<dyn Error>> {
+        Self::load(&Self::jar_path()?, &Self::yggdrasil_url())
+    }
+
+    fn load(jar: &Path, yggdrasil: &str) -> Result<Self, Box<dyn Error>> {
+        if !jar.exists() {
+            return Err("authlib-injector is not downloaded.".into());
+        }
+        let prefetched = fs::read_to_string(jar.with_file_name("yggdrasil.prefetched"))?;
+        debug!(target: AUTHLIB, "Using cached authlib-injector.");
+
+        Ok(Self {
+            jar: jar.to_path_buf(),
+            yggdrasil: yggdrasil.to_string(),
+            prefetched,
+        })
+    }
+
+    fn yggdrasil_url() -> String {
+        format!("{}{}", URL, YGGDRASIL_URL)
+    }
+
+    pub fn to_args(&self) -> Vec<String> {
+        vec![
+            format!(
+                "-javaagent:{}={}",
+                self.jar.to_string_lossy(),
+                self.yggdrasil
+            ),
+            format!("-Dauthlibinjector.yggdrasil.prefetched={}", self.prefetched),
+        ]
+    }
+}
+
+pub fn check_jar(path: &Path, sha256: &str) -> Result<(), Box<dyn Error>> {
+    let checksum = sha256_file(path)?;
+    if checksum != sha256 {
+        error!(target: AUTHLIB, "authlib-injector checksum mismatch. Expected: {sha256} | Got: {checksum}");
+        return Err("authlib-injector checksum mismatch.".into());
+    }
+
+    Ok(())
+}
@@ -1,155 +1,157 @@
-use std::{error::Error, fs::File, sync::mpsc::Sender};
-
-use log::{debug, error, info};
-use reqwest::header::{HeaderValue, RANGE};
-use unzpack::Unzpack;
-
-use crate::{launcher::commands::Command, utils::constants::{GET_MINECRAFT, URL}};
+use std::{error::Error, fs, path::Path, sync::mpsc::Sender};
+
+use log::{error, info, warn};
+use reqwest::blocking::Client;
+
+use crate::{
+    launcher::commands::{Command, DownloadProgress},
+    utils::{
+        archive::{extracted_size, install},
+        cache::{cache_file, remove_cached},
+        constants::GET_MINECRAFT,
+        disk::ensure_space,
+        download::{
+            client, content_length, download, download_small, mirror_urls, CancelToken, Checksum,
+            ChecksumMismatch, DownloadOptions,
+        },
+    },
+};
+
+use super::{
+    instance::Instance,
+    manifest::{PackageInfo, USER_PATHS},
+    mod_manager::user_owned_files,
+};
 
 pub const DOWNLOAD: &str = "MINECRAFT/DOWNLOAD";
 
-struct PartialRangeIter {
-    start: u64,
-    end: u64,
-    buffer_size: u32,
+// Download single file through temp file. Size and sha1 are checked when given.
+pub fn download_file(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    sha1: Option<&str>,
+    size: Option<u64>,
+) -> Result<(), Box<dyn Error>> {
+    let options = DownloadOptions {
+        checksum: sha1.map(|h| Checksum::Sha1(h.to_string())),
+        ..Default::default()
+    };
+
+    download_small(client, url, path, size, &options)
 }
 
-impl PartialRangeIter {
-    pub fn new(start: u64, end: u64, buffer_size: u32) -> Result<Self, Box<dyn Error>> {
-        if buffer_size == 0 {
-            Err("invalid buffer_size, give a value greater than zero.")?;
-        }
-        Ok(PartialRangeIter {
-            start,
-            end,
-            buffer_size,
-        })
-    }
-}
+// Paths relative to game dir which are moved from old game dir on reinstall: player's data and
+// mods added or disabled by user
+pub fn keep_paths(game_dir: &Path, instance: &Instance) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut ret: Vec<String> = USER_PATHS.iter().map(|p| p.to_string()).collect();
 
-impl Iterator for PartialRangeIter {
-    type Item = (HeaderValue, u64);
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.start > self.end {
-            None
-        } else {
-            let prev_start = self.start;
-            self.start += std::cmp::min(self.buffer_size as u64, self.end - self.start + 1);
-            Some((
-                HeaderValue::from_str(&format!("bytes={}-{}", prev_start, self.start - 1))
-                    .expect("string provided by format!"),
-                prev_start,
-            ))
-        }
+    for file in user_owned_files(&game_dir.join("mods"), instance)? {
+        ret.push(format!("mods/{file}"));
     }
-}
 
-pub fn download_minecraft(data_sender: Sender<Command>) -> Result<(), Box<dyn std::error::Error>> {
-    info!(target: DOWNLOAD, "Starting download minecraft");
-
-    const CHUNK_SIZE: u32 = 1000 * 1024;
-    let url = format!("{}{}", URL, GET_MINECRAFT);
-
-    let client = reqwest::blocking::Client::new();
-    let res = match client.get(&url).send() {
-        Ok(r) => Ok(r),
-        Err(e) => {
-            error!(target: DOWNLOAD, "Error while sending request to get download. Error: {e}");
-            Err(e)
-        }
-    }?;
+    Ok(ret)
+}
 
-    if !res.status().is_success() {
-        return Err(format!("Server error: {}", res.status().to_string()).into());
+// Archive size on mirrors. Archive which doesn't match manifest isn't downloaded at all.
+pub fn package_size(
+    client: &Client,
+    urls: &[String],
+    package: &PackageInfo,
+    options: &DownloadOptions,
+) -> Result<u64, Box<dyn Error>> {
+    let size = content_length(client, urls, options)?;
+    if size != package.size {
+        error!(target: DOWNLOAD, "Package size mismatch. Expected: {} | Server: {size}", package.size);
+        return Err(format!(
+            "Game package on server doesn't match manifest: {size} bytes instead of {}.",
+            package.size
+        )
+        .into());
     }
 
-    let size = match res.content_length() {
-        Some(s) => Some(s),
-        None => {
-            error!(target: DOWNLOAD, "Couldn't get content length.");
-            None
-        }
-    }
-    .ok_or("Couldn't get content length.")?;
-    debug!(target: DOWNLOAD, "Content length: {size}");
-
-    let mut path = match dirs::download_dir() {
-        Some(d) => Some(d),
-        None => {
-            error!(target: DOWNLOAD, "Couldn't get download dir.");
-            None
+    Ok(size)
+}
+
+// Archive with wrong checksum is removed by `download` and downloaded again, up to retry policy
+// attempts
+pub fn download_package(
+    client: &Client,
+    urls: &[String],
+    path: &Path,
+    options: &DownloadOptions,
+    mut progress: impl FnMut(DownloadProgress) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match download(client, urls, path, options, &mut progress) {
+            Err(e) if e.is::<ChecksumMismatch>() && attempt < options.retry.attempts => {
+                warn!(target: DOWNLOAD, "Downloaded package is corrupted, retry {attempt}.");
+                options.retry.sleep(attempt);
+            }
+            r => break r,
         }
     }
-    .ok_or("Couldn't get download dir.")?;
-    path.push("funnycraft.zip");
+}
 
-    let mut file = match File::create(&path) {
-        Ok(f) => Ok(f),
-        Err(e) => {
-            error!(target: DOWNLOAD, "Couldn't create download file. Error: {e}");
-            Err(e)
-        }
-    }?;
+// Archive is checked against published size and sha256 before extraction. Corrupted one is
+// downloaded again. `unpacked_size` is size of unpacked game from manifest, used for pre-flight
+// space check.
+pub fn download_minecraft(
+    data_sender: Sender<Command>,
+    cancel: &CancelToken,
+    package: &PackageInfo,
+    unpacked_size: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!(target: DOWNLOAD, "Starting download minecraft");
 
-    let p_iter = match PartialRangeIter::new(0, size - 1, CHUNK_SIZE) {
-        Ok(p) => Ok(p),
-        Err(e) => {
-            error!(target: DOWNLOAD, "Error in PartialRangeIter initialize: {e}");
-            Err(e)
-        }
-    }?;
-    for (range, downloaded) in p_iter {
-        match data_sender.send(Command::DOWNLOAD((downloaded, size))) {
+    let urls = mirror_urls(GET_MINECRAFT);
+    let client = client()?;
+
+    let path = cache_file("funnycraft.zip")?;
+    let game_dir = Instance::game_dir()?;
+    let options = DownloadOptions {
+        checksum: Some(Checksum::Sha256(package.sha256.clone())),
+        cancel: cancel.clone(),
+        ..Default::default()
+    };
+
+    // Cache and game dir are both in OS data dir, archive is unpacked before it's removed
+    let size = package_size(&client, &urls, package, &options)?;
+    let partial = fs::metadata(&path).map(|m| m.len()).unwrap_or(0).min(size);
+    ensure_space(&game_dir, size - partial + unpacked_size)?;
+
+    download_package(
+        &client,
+        &urls,
+        &path,
+        &options,
+        |progress| match data_sender.send(Command::DOWNLOAD(progress)) {
             Ok(_) => Ok(()),
             Err(e) => {
                 error!(target: DOWNLOAD, "Error while sending \"DOWNLOAD\" command to control thread.");
-                Err(e)
-            }
-        }?;
-        let mut response = match client.get(&url).header(RANGE, range).send() {
-            Ok(r) => Ok(r),
-            Err(e) => {
-                error!(target: DOWNLOAD, "Error while sending download data request.");
-                Err(e)
+                Err(e.into())
             }
-        }?;
-
-        if !response.status().is_success() {
-            return Err(format!("Server error: {}", response.status().to_string()).into());
-        }
-
-        match std::io::copy(&mut response, &mut file) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                error!(target: DOWNLOAD, "Error while writing downloaded data to file.");
-                Err(e)
-            }
-        }?;
-    }
+        },
+    )?;
 
     info!(target: DOWNLOAD, "Download completed.");
 
-    drop(file);
-
     info!(target: DOWNLOAD, "Starting unziping game...");
 
-    let outpath = match dirs::data_dir() {
-        Some(d) => Some(d),
-        None => {
-            error!(target: DOWNLOAD, "Couldn't get data dir.");
-            None
-        }
-    }
-    .ok_or("Couldn't get data dir.")?;
-
-    match data_sender.send(Command::UNZIPING) {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            error!(target: DOWNLOAD, "Error while sending \"UNZIPING\" command to control thread.");
-            Err(e)
+    ensure_space(&game_dir, extracted_size(&path)?)?;
+    let keep = keep_paths(&game_dir, &Instance::load()?)?;
+    let keep: Vec<&str> = keep.iter().map(String::as_str).collect();
+    match install(&path, &game_dir, &keep, |progress| {
+        match data_sender.send(Command::UNZIPING(progress)) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!(target: DOWNLOAD, "Error while sending \"UNZIPING\" command to control thread.");
+                Err(e.into())
+            }
         }
-    }?;
-    match Unzpack::extract(&path, &outpath) {
+    }) {
         Ok(_) => Ok(()),
         Err(e) => {
             error!(target: DOWNLOAD, "Error while unziping game. Error: {e}");
@@ -158,6 +160,7 @@ pub fn download_minecraft(data_sender: Sender<Command>) -> Result<(), Box<dyn st
     }?;
 
     info!(target: DOWNLOAD, "Game was unzipped.");
+    remove_cached(&path);
 
     match data_sender.send(Command::PLAY) {
         Ok(_) => Ok(()),
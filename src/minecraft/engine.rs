@@ -0,0 +1,478 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info, warn};
+use reqwest::{
+    blocking::Client,
+    header::{HeaderValue, RANGE},
+    StatusCode, Url,
+};
+
+use crate::{
+    launcher::commands::DownloadProgress,
+    utils::{
+        disk::ensure_space,
+        download::{
+            is_cancelled, send, verify, CancelToken, Cancelled, Checksum, ChecksumMismatch,
+            RetryPolicy, SpeedMeter,
+        },
+        http::client_builder,
+    },
+};
+
+pub const ENGINE: &str = "MINECRAFT/ENGINE";
+
+// GUI handles one command per frame, so progress is not sent more often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const BUFFER_SIZE: usize = 64 * 1024;
+
+pub struct Task {
+    // Same file on every mirror, in order of preference
+    pub urls: Vec<String>,
+    pub path: PathBuf,
+    pub sha1: Option<String>,
+    pub size: Option<u64>,
+}
+
+pub struct EngineOptions {
+    // Concurrent connections overall
+    pub connections: usize,
+    // Concurrent connections to one host
+    pub per_host: usize,
+    // Files bigger than this are downloaded by ranges in parallel
+    pub chunk_size: u64,
+    pub retry: RetryPolicy,
+    pub cancel: CancelToken,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            connections: 8,
+            per_host: 4,
+            chunk_size: 8 * 1024 * 1024,
+            retry: RetryPolicy::default(),
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+struct Job {
+    task: usize,
+    // Host of the mirror the job is counted on, chosen when job is taken
+    host: String,
+    // Inclusive byte range of ranged part
+    range: Option<(u64, u64)>,
+}
+
+struct Queue {
+    jobs: VecDeque<Job>,
+    active: HashMap<String, usize>,
+}
+
+// State shared between workers of one run
+struct Run<'a> {
+    tasks: &'a [Task],
+    queue: Mutex<Queue>,
+    available: Condvar,
+    // Ranged parts left per task, last finished part completes the file
+    parts_left: Vec<AtomicU64>,
+    bytes: AtomicU64,
+    files: AtomicU64,
+    total_bytes: u64,
+    failed: AtomicBool,
+    error: Mutex<Option<String>>,
+    // Hosts of mirrors which failed in this run. Next jobs start from healthy ones.
+    unhealthy: Mutex<HashSet<String>>,
+    last_progress: Mutex<Instant>,
+    meter: Mutex<SpeedMeter>,
+}
+
+pub struct DownloadEngine {
+    client: Client,
+    options: EngineOptions,
+}
+
+// Mirror identity: host with explicit port
+fn host(url: &str) -> String {
+    let url = match Url::parse(url) {
+        Ok(u) => u,
+        Err(_) => return String::new(),
+    };
+
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        _ => String::new(),
+    }
+}
+
+impl Run<'_> {
+    // Task urls with unhealthy mirrors moved to the end, they are still tried as last resort
+    fn urls(&self, task: &Task) -> Vec<String> {
+        let unhealthy = self.unhealthy.lock().unwrap();
+        let (healthy, failed): (Vec<_>, Vec<_>) = task
+            .urls
+            .iter()
+            .cloned()
+            .partition(|u| !unhealthy.contains(&host(u)));
+
+        healthy.into_iter().chain(failed).collect()
+    }
+}
+
+fn tmp_path(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(path.with_file_name(format!(
+        "{}.tmp",
+        path.file_name()
+            .and_then(|f| f.to_str())
+            .ok_or("Wrong file name.")?
+    )))
+}
+
+impl DownloadEngine {
+    pub fn new(options: EngineOptions) -> Result<Self, Box<dyn Error>> {
+        // Idle connections are kept and reused by workers
+        let client = client_builder()?
+            .pool_max_idle_per_host(options.per_host)
+            .build()?;
+
+        Ok(Self { client, options })
+    }
+
+    // Download all tasks. Progress is aggregated over all workers.
+    pub fn run(
+        &self,
+        tasks: &[Task],
+        progress: impl Fn(DownloadProgress) -> Result<(), Box<dyn Error>> + Sync,
+    ) -> Result<(), Box<dyn Error>> {
+        if tasks.is_empty() {
+            return Ok(());
+        }
+
+        // Files are written next to where they are installed
+        let total_bytes = tasks.iter().filter_map(|t| t.size).sum();
+        if let Some(dir) = tasks[0].path.parent() {
+            ensure_space(dir, total_bytes)?;
+        }
+
+        let mut jobs = VecDeque::new();
+        let mut parts_left = Vec::new();
+
+        for (i, task) in tasks.iter().enumerate() {
+            let url = task.urls.first().ok_or("No download urls.")?;
+            Url::parse(url)?;
+
+            if let Some(parent) = task.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            match task.size {
+                Some(size) if size > self.options.chunk_size => {
+                    // Parts are written into preallocated temp file
+                    File::create(tmp_path(&task.path)?)?.set_len(size)?;
+
+                    let mut start = 0;
+                    let mut parts = 0;
+                    while start < size {
+                        let end = (start + self.options.chunk_size).min(size) - 1;
+                        jobs.push_back(Job {
+                            task: i,
+                            host: String::new(),
+                            range: Some((start, end)),
+                        });
+                        start = end + 1;
+                        parts += 1;
+                    }
+                    parts_left.push(AtomicU64::new(parts));
+                }
+                _ => {
+                    jobs.push_back(Job {
+                        task: i,
+                        host: String::new(),
+                        range: None,
+                    });
+                    parts_left.push(AtomicU64::new(1));
+                }
+            }
+        }
+
+        let workers = self.options.connections.max(1).min(jobs.len());
+        info!(target: ENGINE, "Downloading {} files with {workers} connections...", tasks.len());
+
+        let run = Run {
+            tasks,
+            queue: Mutex::new(Queue {
+                jobs,
+                active: HashMap::new(),
+            }),
+            available: Condvar::new(),
+            parts_left,
+            bytes: AtomicU64::new(0),
+            files: AtomicU64::new(0),
+            total_bytes,
+            failed: AtomicBool::new(false),
+            error: Mutex::new(None),
+            unhealthy: Mutex::new(HashSet::new()),
+            meter: Mutex::new(SpeedMeter::new(0)),
+            last_progress: Mutex::new(
+                Instant::now()
+                    .checked_sub(PROGRESS_INTERVAL)
+                    .unwrap_or_else(Instant::now),
+            ),
+        };
+
+        std::thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| self.worker(&run, &progress));
+            }
+        });
+
+        if let Some(e) = run.error.lock().unwrap().take() {
+            // Unfinished files are not resumed by engine
+            for (task, parts) in tasks.iter().zip(&run.parts_left) {
+                let tmp = tmp_path(&task.path)?;
+                if parts.load(Ordering::Relaxed) > 0 && tmp.exists() {
+                    fs::remove_file(tmp)?;
+                }
+            }
+
+            if self.options.cancel.is_cancelled() {
+                info!(target: ENGINE, "Download cancelled.");
+                return Err(Cancelled.into());
+            }
+            return Err(e.into());
+        }
+
+        self.report(&run, &progress, "", true)?;
+        info!(target: ENGINE, "Download completed.");
+
+        Ok(())
+    }
+
+    fn report(
+        &self,
+        run: &Run,
+        progress: &(impl Fn(DownloadProgress) -> Result<(), Box<dyn Error>> + Sync),
+        file: &str,
+        force: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        {
+            let mut last = run.last_progress.lock().unwrap();
+            if !force && last.elapsed() < PROGRESS_INTERVAL {
+                return Ok(());
+            }
+            *last = Instant::now();
+        }
+
+        let bytes = run.bytes.load(Ordering::Relaxed);
+        progress(DownloadProgress {
+            file: file.to_string(),
+            files: run.files.load(Ordering::Relaxed),
+            total_files: run.tasks.len() as u64,
+            bytes,
+            total_bytes: run.total_bytes,
+            speed: run.meter.lock().unwrap().update(bytes),
+            ..Default::default()
+        })
+    }
+
+    // Take next job whose current mirror has free connections, waits if all are busy
+    fn next_job(&self, run: &Run) -> Option<Job> {
+        let mut queue = run.queue.lock().unwrap();
+
+        loop {
+            if run.failed.load(Ordering::Relaxed) || queue.jobs.is_empty() {
+                return None;
+            }
+
+            let per_host = self.options.per_host.max(1);
+            let free = queue.jobs.iter().enumerate().find_map(|(i, j)| {
+                let host = host(run.urls(&run.tasks[j.task]).first()?);
+                (queue.active.get(&host).copied().unwrap_or(0) < per_host).then_some((i, host))
+            });
+
+            if let Some((i, host)) = free {
+                let mut job = queue.jobs.remove(i)?;
+                *queue.active.entry(host.clone()).or_insert(0) += 1;
+                job.host = host;
+                return Some(job);
+            }
+
+            queue = run.available.wait(queue).unwrap();
+        }
+    }
+
+    fn release(&self, run: &Run, job: &Job) {
+        let mut queue = run.queue.lock().unwrap();
+        if let Some(n) = queue.active.get_mut(&job.host) {
+            *n -= 1;
+        }
+        run.available.notify_all();
+    }
+
+    // Job failed over to another mirror, its connection is counted there
+    fn switch_host(&self, run: &Run, job: &mut Job, host: String) {
+        let mut queue = run.queue.lock().unwrap();
+        if let Some(n) = queue.active.get_mut(&job.host) {
+            *n -= 1;
+        }
+        *queue.active.entry(host.clone()).or_insert(0) += 1;
+        job.host = host;
+        run.available.notify_all();
+    }
+
+    fn worker(
+        &self,
+        run: &Run,
+        progress: &(impl Fn(DownloadProgress) -> Result<(), Box<dyn Error>> + Sync),
+    ) {
+        while let Some(mut job) = self.next_job(run) {
+            let mut retry = 0;
+            let result = loop {
+                let mut counted = 0;
+                match self.download(run, &mut job, progress, &mut counted) {
+                    // Connection was broken while reading body
+                    Err(e)
+                        if e.is::<std::io::Error>()
+                            && retry + 1 < self.options.retry.attempts
+                            && !run.failed.load(Ordering::Relaxed) =>
+                    {
+                        run.bytes.fetch_sub(counted, Ordering::Relaxed);
+                        retry += 1;
+                        warn!(target: ENGINE, "Download interrupted, retry {retry}. Error: {e}");
+                        self.options.retry.sleep(retry);
+                    }
+                    r => break r,
+                }
+            };
+            self.release(run, &job);
+
+            if let Err(e) = result {
+                let url = &run.tasks[job.task].urls[0];
+                if !is_cancelled(e.as_ref()) {
+                    error!(target: ENGINE, "Error while downloading \"{url}\". Error: {e}");
+                }
+
+                let mut error = run.error.lock().unwrap();
+                if error.is_none() {
+                    *error = Some(e.to_string());
+                }
+                run.failed.store(true, Ordering::Relaxed);
+                run.available.notify_all();
+                return;
+            }
+        }
+    }
+
+    fn download(
+        &self,
+        run: &Run,
+        job: &mut Job,
+        progress: &(impl Fn(DownloadProgress) -> Result<(), Box<dyn Error>> + Sync),
+        counted: &mut u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let task = &run.tasks[job.task];
+        let tmp = tmp_path(&task.path)?;
+        let file_name = task
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        let range = match job.range {
+            Some((start, end)) => Some(HeaderValue::from_str(&format!("bytes={start}-{end}"))?),
+            None => None,
+        };
+        let urls = run.urls(task);
+        let mut mirror = 0;
+        let result = send(
+            &urls,
+            &mut mirror,
+            &self.options.retry,
+            &self.options.cancel,
+            |url| {
+                let mut request = self.client.get(url);
+                if let Some(range) = &range {
+                    request = request.header(RANGE, range.clone());
+                }
+                request.send()
+            },
+        );
+
+        // Other jobs don't wait for retries of failed mirrors
+        if mirror > 0 {
+            let mut unhealthy = run.unhealthy.lock().unwrap();
+            for url in &urls[..mirror.min(urls.len())] {
+                if unhealthy.insert(host(url)) {
+                    warn!(target: ENGINE, "Mirror \"{}\" is unhealthy, switching to next one.", host(url));
+                }
+            }
+        }
+        let mut resp = result?;
+        if let Some(url) = urls.get(mirror) {
+            if host(url) != job.host {
+                self.switch_host(run, job, host(url));
+            }
+        }
+
+        let mut file = match job.range {
+            Some((start, _)) => {
+                if resp.status() != StatusCode::PARTIAL_CONTENT {
+                    return Err("Server doesn't support range requests.".into());
+                }
+                let mut file = OpenOptions::new().write(true).open(&tmp)?;
+                file.seek(SeekFrom::Start(start))?;
+                file
+            }
+            None => File::create(&tmp)?,
+        };
+
+        let mut buffer = vec![0; BUFFER_SIZE];
+        loop {
+            self.options.cancel.wait_if_paused()?;
+            if run.failed.load(Ordering::Relaxed) {
+                return Err("Download aborted.".into());
+            }
+
+            let n = resp.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buffer[..n])?;
+            run.bytes.fetch_add(n as u64, Ordering::Relaxed);
+            *counted += n as u64;
+            self.report(run, progress, &file_name, false)?;
+        }
+        drop(file);
+
+        // Whole file is ready when its last part is written
+        if run.parts_left[job.task].fetch_sub(1, Ordering::AcqRel) == 1 {
+            let checksum = task.sha1.clone().map(Checksum::Sha1);
+            if !verify(&tmp, task.size, checksum.as_ref())? {
+                fs::remove_file(&tmp)?;
+                return Err(ChecksumMismatch {
+                    file: file_name.clone(),
+                }
+                .into());
+            }
+
+            fs::rename(&tmp, &task.path)?;
+            run.files.fetch_add(1, Ordering::Relaxed);
+            debug!(target: ENGINE, "Downloaded {:?}.", task.path);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, error::Error, fs, path::PathBuf};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::constants::{LAUNCHER_DIR, MINECRAFT_FORGE, MINECRAFT_VERSION, WORKING_DIR};
+
+pub const INSTANCE: &str = "MINECRAFT/INSTANCE";
+
+pub const FORGE: &str = "forge";
+pub const FABRIC: &str = "fabric-loader";
+
+// Per-instance launch settings. Stored in launcher dir, so game files are not touched.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Instance {
+    pub name: String,
+    pub minecraft_version: String,
+    // Empty loader means vanilla
+    pub loader: String,
+    pub loader_version: String,
+    // Launch with authlib-injector agent, only for servers which use launcher's session server
+    pub authlib_injector: bool,
+    // Mods installed by server sync. Everything else in mods dir is user's.
+    pub managed_mods: Vec<String>,
+    // Mods added by user from launcher. Sync never deletes them.
+    pub user_mods: Vec<String>,
+    // User choices for optional mods by filename
+    pub optional_mods: HashMap<String, bool>,
+    // Game files installed from file manifest. Only they could be removed as obsolete.
+    pub managed_files: Vec<String>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        let loader_version = MINECRAFT_FORGE
+            .split_once("-forge-")
+            .map(|(_, v)| v.to_string())
+            .unwrap_or_default();
+
+        Self {
+            name: String::from("FunnyCraft"),
+            minecraft_version: MINECRAFT_VERSION.to_string(),
+            loader: FORGE.to_string(),
+            loader_version,
+            authlib_injector: false,
+            managed_mods: Vec::new(),
+            user_mods: Vec::new(),
+            optional_mods: HashMap::new(),
+            managed_files: Vec::new(),
+        }
+    }
+}
+
+impl Instance {
+    // Name of version dir and json: "1.16.5-forge-36.2.34", "fabric-loader-0.14.21-1.16.5"
+    pub fn version_id(&self) -> String {
+        match self.loader.as_str() {
+            FORGE => format!("{}-forge-{}", self.minecraft_version, self.loader_version),
+            FABRIC => format!(
+                "fabric-loader-{}-{}",
+                self.loader_version, self.minecraft_version
+            ),
+            _ => self.minecraft_version.clone(),
+        }
+    }
+
+    pub fn game_dir() -> Result<PathBuf, Box<dyn Error>> {
+        Ok(dirs::data_dir()
+            .ok_or("OS data dir not found.")?
+            .join(WORKING_DIR))
+    }
+
+    fn config_path() -> Result<PathBuf, Box<dyn Error>> {
+        let path = dirs::data_dir()
+            .ok_or("OS data dir not found.")?
+            .join(LAUNCHER_DIR);
+
+        if !path.exists() {
+            fs::create_dir_all(&path)?;
+        }
+
+        Ok(path.join("instance.json"))
+    }
+
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let path = Self::config_path()?;
+
+        if !path.exists() {
+            warn!(target: INSTANCE, "Instance config not found. Using default.");
+            return Ok(Self::default());
+        }
+
+        let data = match fs::read_to_string(&path) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Couldn't read instance config. Error: {e}").into()),
+        };
+
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::config_path()?;
+
+        if let Err(e) = fs::write(path, serde_json::to_string(self)?) {
+            return Err(format!("Couldn't write instance config. Error: {e}").into());
+        }
+
+        Ok(())
+    }
+}
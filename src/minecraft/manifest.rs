@@ -0,0 +1,237 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    archive::safe_join,
+    constants::{GET_MINECRAFT_FILES, GET_MINECRAFT_MANIFEST, URL},
+    download::mirror_urls,
+    hash::sha1_file,
+    hash_cache::HashCache,
+    http::client_builder,
+};
+
+pub const MANIFEST: &str = "MINECRAFT/MANIFEST";
+
+// Top level paths of game dir which belong to player and are never validated
+pub const USER_PATHS: [&str; 5] = [
+    "saves",
+    "screenshots",
+    "logs",
+    "crash-reports",
+    "options.txt",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    // File must always match manifest
+    #[default]
+    Always,
+    // File is only created if missing, user could change it later (e.g. configs)
+    Once,
+    // File is not checked at all
+    Ignore,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+    // Relative to game dir, always with "/" separator
+    pub path: String,
+    pub sha1: String,
+    pub size: u64,
+    #[serde(default)]
+    pub policy: Policy,
+    // Served from GET_MINECRAFT_FILES by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl FileEntry {
+    pub fn urls(&self) -> Vec<String> {
+        match &self.url {
+            Some(u) => vec![u.clone()],
+            None => mirror_urls(&format!("{}/{}", GET_MINECRAFT_FILES, self.path)),
+        }
+    }
+}
+
+// Full game archive, used for fresh install
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageInfo {
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FileManifest {
+    pub version: String,
+    pub files: Vec<FileEntry>,
+    // Archive couldn't be installed without it, but files still could be updated one by one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<PackageInfo>,
+}
+
+#[derive(Debug, Default)]
+pub struct ManifestDiff {
+    pub missing: Vec<FileEntry>,
+    pub modified: Vec<FileEntry>,
+    // Files which are not in manifest, relative to game dir
+    pub extra: Vec<String>,
+}
+
+impl ManifestDiff {
+    // Extra files don't break the game, so they are only reported
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty()
+    }
+}
+
+pub fn is_user_path(path: &str) -> bool {
+    let top = path.split('/').next().unwrap_or_default();
+    USER_PATHS.contains(&top)
+}
+
+// All files under `dir` recursively, as paths relative to `base`
+pub fn walk(base: &Path, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut ret = Vec::new();
+
+    if dir.is_file() {
+        ret.push(dir.strip_prefix(base)?.to_path_buf());
+        return Ok(ret);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            ret.append(&mut walk(base, &path)?);
+        } else {
+            ret.push(path.strip_prefix(base)?.to_path_buf());
+        }
+    }
+
+    ret.sort();
+
+    Ok(ret)
+}
+
+pub fn to_manifest_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl FileManifest {
+    // Hash given files of game dir
+    pub fn generate(
+        version: impl Into<String>,
+        game_dir: &Path,
+        files: &[PathBuf],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut entries = Vec::new();
+
+        for file in files {
+            let path = game_dir.join(file);
+            entries.push(FileEntry {
+                path: to_manifest_path(file),
+                sha1: sha1_file(&path)?,
+                size: fs::metadata(&path)?.len(),
+                policy: Policy::Always,
+                url: None,
+            });
+        }
+
+        Ok(Self {
+            version: version.into(),
+            files: entries,
+            package: None,
+        })
+    }
+
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let client = client_builder()?.timeout(Duration::from_secs(5)).build()?;
+
+        let resp = match client
+            .get(format!("{}{}", URL, GET_MINECRAFT_MANIFEST))
+            .send()
+        {
+            Ok(r) => Ok(r),
+            Err(e) => {
+                error!(target: MANIFEST, "Error while requesting file manifest. Error: {e}");
+                Err(e)
+            }
+        }?;
+
+        if !resp.status().is_success() {
+            return Err(format!("Server error: {}", resp.status()).into());
+        }
+
+        Ok(serde_json::from_str(&resp.text()?)?)
+    }
+
+    // Compare manifest with game dir. `excluded` are paths which are managed elsewhere (e.g.
+    // mods), they are never reported as extra. Hashes of unchanged files are taken from cache.
+    pub fn diff(
+        &self,
+        game_dir: &Path,
+        excluded: &[String],
+        cache: &mut HashCache,
+    ) -> Result<ManifestDiff, Box<dyn Error>> {
+        let mut diff = ManifestDiff::default();
+        let mut listed = HashSet::new();
+        let mut check = Vec::new();
+
+        for entry in &self.files {
+            listed.insert(entry.path.as_str());
+
+            if entry.policy == Policy::Ignore || is_user_path(&entry.path) {
+                continue;
+            }
+
+            let path = safe_join(game_dir, &entry.path)?;
+            if !path.is_file() {
+                diff.missing.push(entry.clone());
+                continue;
+            }
+
+            if entry.policy == Policy::Once {
+                continue;
+            }
+
+            if fs::metadata(&path)?.len() != entry.size {
+                diff.modified.push(entry.clone());
+                continue;
+            }
+            check.push((entry, path));
+        }
+
+        let paths: Vec<PathBuf> = check.iter().map(|(_, p)| p.clone()).collect();
+        for ((entry, _), sha1) in check.iter().zip(cache.sha1_files(&paths)?) {
+            if !sha1.eq_ignore_ascii_case(&entry.sha1) {
+                diff.modified.push((*entry).clone());
+            }
+        }
+
+        if game_dir.exists() {
+            for file in walk(game_dir, game_dir)? {
+                let path = to_manifest_path(&file);
+                if !listed.contains(path.as_str())
+                    && !is_user_path(&path)
+                    && !excluded.contains(&path)
+                {
+                    diff.extra.push(path);
+                }
+            }
+        }
+
+        Ok(diff)
+    }
+}
@@ -1,20 +1,40 @@
-use crate::utils::constants::{MINECRAFT_FORGE, MINECRAFT_VERSION, WORKING_DIR};
+use crate::{
+    api::session::Session,
+    utils::{
+        constants::{MINECRAFT_FORGE, WORKING_DIR},
+        http::proxy_config,
+    },
+};
 use std::{
     error::Error,
     fs,
     process::{Child, Command},
 };
 
-use self::minecraft_json::MinecraftJson;
+use self::{authlib::AuthlibInjector, instance::Instance, minecraft_json::MinecraftJson};
 
+pub mod authlib;
 pub mod downloader;
+pub mod engine;
+pub mod instance;
+pub mod manifest;
 pub mod minecraft_json;
+pub mod mod_inspector;
+pub mod mod_manager;
+pub mod modpack;
+pub mod mods;
+pub mod offline;
+pub mod repair;
+#[cfg(test)]
 mod tests;
+pub mod updater;
 pub mod validate;
 
 pub const MINECRAFT: &str = "MINECRAFT";
 // Arguments to launch java
 pub struct JvmOptions {
+    agent_args: Vec<String>,
+    proxy_args: Vec<String>,
     native_path: String,
     launcher_name: String,
     launcher_version: String,
@@ -24,6 +44,8 @@ pub struct JvmOptions {
 impl Default for JvmOptions {
     fn default() -> Self {
         Self {
+            agent_args: Vec::new(),
+            proxy_args: Vec::new(),
             native_path: String::new(),
             launcher_name: "RuLauncher".to_string(),
             launcher_version: "OBT".to_string(),
@@ -34,13 +56,16 @@ impl Default for JvmOptions {
 
 impl JvmOptions {
     pub fn to_args(&self) -> Vec<String> {
-        vec![
+        let mut args = self.agent_args.clone();
+        args.extend(self.proxy_args.iter().cloned());
+        args.append(&mut vec![
             "-Djava.library.path=".to_string() + &self.native_path,
             "-Dminecraft.launcher.brand=".to_string() + &self.launcher_name,
             "-Dminecraft.launcher.version=".to_string() + &self.launcher_version,
             "-cp".to_string(),
             self.libs.clone(),
-        ]
+        ]);
+        args
     }
 }
 
@@ -62,14 +87,14 @@ pub struct GameOptions {
 impl Default for GameOptions {
     fn default() -> Self {
         Self {
-            username: "MUTS04".to_string(),
+            username: String::new(),
             version: MINECRAFT_FORGE.to_string(),
             game_dir: String::new(),
             assets_dir: String::new(),
             assets_index: "".to_string(),
-            uuid: "TmlsbA==".to_string(),
-            access_token: "dummy_token".to_string(),
-            user_type: "OFFLINE".to_string(),
+            uuid: String::new(),
+            access_token: String::new(),
+            user_type: "mojang".to_string(),
             version_type: "release".to_string(),
             server: "localhost".to_string(),
             port: "25565".to_string(),
@@ -78,6 +103,13 @@ impl Default for GameOptions {
 }
 
 impl GameOptions {
+    pub fn set_session(&mut self, session: &Session) {
+        self.username = session.username.clone();
+        self.uuid = session.uuid.clone();
+        self.access_token = session.access_token.clone();
+        self.user_type = session.user_type.clone();
+    }
+
     pub fn to_args(&self) -> Vec<String> {
         vec![
             "--username".to_string(),
@@ -115,22 +147,24 @@ pub struct Minecraft {
 }
 
 impl Minecraft {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
+    pub fn new(session: &Session, instance: &Instance) -> Result<Self, Box<dyn Error>> {
         let working_path = dirs::data_dir()
             .ok_or("OS data dir not found.")?
             .join(WORKING_DIR);
 
         let version_path = working_path.clone().join("versions");
+        let version_id = instance.version_id();
+        let mc_version = &instance.minecraft_version;
 
         let forge_file = version_path
             .clone()
-            .join(MINECRAFT_FORGE)
-            .join(format!("{}.json", MINECRAFT_FORGE));
+            .join(&version_id)
+            .join(format!("{}.json", version_id));
 
         let mc_file = version_path
             .clone()
-            .join(MINECRAFT_VERSION)
-            .join(format!("{}.json", MINECRAFT_VERSION));
+            .join(mc_version)
+            .join(format!("{}.json", mc_version));
 
         let forge_raw_data = match fs::read_to_string(&forge_file) {
             Ok(f) => f,
@@ -155,9 +189,10 @@ impl Minecraft {
         let mc_data = MinecraftJson::new(&mc_raw_data)?;
 
         let mut jvm_options = JvmOptions::default();
+        jvm_options.proxy_args = proxy_config().jvm_args();
 
         jvm_options.native_path = version_path
-            .join(MINECRAFT_VERSION)
+            .join(mc_version)
             .join("natives")
             .to_str()
             .ok_or("Path to native libraries not found. Check your client.")?
@@ -175,8 +210,8 @@ impl Minecraft {
             .to_string();
 
         let game_path = version_path
-            .join(MINECRAFT_VERSION)
-            .join(format!("{}.jar", MINECRAFT_VERSION))
+            .join(mc_version)
+            .join(format!("{}.jar", mc_version))
             .to_str()
             .ok_or("Minecraft not found. Check your client.")?
             .to_string();
@@ -189,6 +224,8 @@ impl Minecraft {
         );
 
         let mut game_options = GameOptions::default();
+        game_options.set_session(session);
+        game_options.version = version_id;
 
         game_options.game_dir = working_path
             .to_str()
@@ -215,6 +252,10 @@ impl Minecraft {
         })
     }
 
+    pub fn set_authlib_injector(&mut self, injector: &AuthlibInjector) {
+        self.jvm_options.agent_args = injector.to_args();
+    }
+
     pub fn run(&self) -> Result<Child, Box<dyn Error>> {
         // javaw %jvm config% %jvm args% "%libs+minecraft%" %main_class% %forge args% %game args%
         let mut args: Vec<String> = Vec::new();
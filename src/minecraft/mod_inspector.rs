@@ -0,0 +1,552 @@
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+use log::{debug, warn};
+use serde_json::Value;
+use zip::ZipArchive;
+
+use super::instance::{Instance, FORGE};
+
+pub const INSPECTOR: &str = "MINECRAFT/INSPECTOR";
+
+// Mod ids provided by game and loader, not by jars in mods dir
+const JAVA: &str = "java";
+const FABRIC_LOADER: &str = "fabricloader";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub mod_id: String,
+    pub range: VersionRange,
+    pub mandatory: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModInfo {
+    pub filename: String,
+    pub mod_id: String,
+    pub version: String,
+    pub display_name: String,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DependencyIssue {
+    Missing {
+        mod_name: String,
+        dependency: String,
+        range: VersionRange,
+    },
+    Incompatible {
+        mod_name: String,
+        dependency: String,
+        range: VersionRange,
+        found: String,
+    },
+}
+
+impl Display for DependencyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyIssue::Missing {
+                mod_name,
+                dependency,
+                range,
+            } => write!(
+                f,
+                "mod {mod_name} needs {dependency} {range}, but it's missing"
+            ),
+            DependencyIssue::Incompatible {
+                mod_name,
+                dependency,
+                range,
+                found,
+            } => write!(
+                f,
+                "mod {mod_name} needs {dependency} {range}, found {found}"
+            ),
+        }
+    }
+}
+
+// Compare versions like "1.16.5", "36.2.34", "2.0-beta.1". Missing parts are zeros.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<String> {
+        v.split('+')
+            .next()
+            .unwrap_or_default()
+            .split(['.', '-', '_'])
+            .map(|p| p.to_string())
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).map(|s| s.as_str()).unwrap_or("0");
+        let y = b.get(i).map(|s| s.as_str()).unwrap_or("0");
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            // Release is newer than pre-release: "1.0" > "1.0-beta"
+            (Ok(_), Err(_)) => Ordering::Greater,
+            (Err(_), Ok(_)) => Ordering::Less,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    Ordering::Equal
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bound {
+    pub version: String,
+    pub inclusive: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Interval {
+    pub lower: Option<Bound>,
+    pub upper: Option<Bound>,
+}
+
+impl Interval {
+    fn contains(&self, version: &str) -> bool {
+        let lower =
+            self.lower
+                .as_ref()
+                .is_none_or(|b| match compare_versions(version, &b.version) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => b.inclusive,
+                    Ordering::Less => false,
+                });
+        let upper =
+            self.upper
+                .as_ref()
+                .is_none_or(|b| match compare_versions(version, &b.version) {
+                    Ordering::Less => true,
+                    Ordering::Equal => b.inclusive,
+                    Ordering::Greater => false,
+                });
+
+        lower && upper
+    }
+
+    fn at_least(version: &str, inclusive: bool) -> Self {
+        Self {
+            lower: Some(Bound {
+                version: version.to_string(),
+                inclusive,
+            }),
+            upper: None,
+        }
+    }
+
+    fn below(version: &str, inclusive: bool) -> Self {
+        Self {
+            lower: None,
+            upper: Some(Bound {
+                version: version.to_string(),
+                inclusive,
+            }),
+        }
+    }
+
+    fn between(from: &str, to: &str) -> Self {
+        Self {
+            lower: Interval::at_least(from, true).lower,
+            upper: Interval::below(to, false).upper,
+        }
+    }
+
+    fn intersect(mut self, other: Interval) -> Self {
+        if other.lower.is_some() {
+            self.lower = other.lower;
+        }
+        if other.upper.is_some() {
+            self.upper = other.upper;
+        }
+        self
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.lower, &self.upper) {
+            (None, None) => write!(f, "*"),
+            (Some(l), Some(u)) if l.version == u.version && l.inclusive && u.inclusive => {
+                write!(f, "{}", l.version)
+            }
+            (lower, upper) => {
+                let mut parts = Vec::new();
+                if let Some(l) = lower {
+                    parts.push(format!(
+                        "{} {}",
+                        if l.inclusive { ">=" } else { ">" },
+                        l.version
+                    ));
+                }
+                if let Some(u) = upper {
+                    parts.push(format!(
+                        "{} {}",
+                        if u.inclusive { "<=" } else { "<" },
+                        u.version
+                    ));
+                }
+                write!(f, "{}", parts.join(" and "))
+            }
+        }
+    }
+}
+
+// Union of intervals. Empty range matches any version.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VersionRange(pub Vec<Interval>);
+
+impl VersionRange {
+    pub fn any() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn contains(&self, version: &str) -> bool {
+        self.0.is_empty() || self.0.iter().any(|i| i.contains(version))
+    }
+
+    // Maven range used by Forge: "[36,)", "[1.16.5,1.17)", "(,2.0]", "[1.0]" or "1.0" (any)
+    pub fn from_maven(range: &str) -> Self {
+        let range = range.trim();
+        if range.is_empty() || range == "*" || !range.starts_with(['[', '(']) {
+            return Self::any();
+        }
+
+        let mut intervals = Vec::new();
+        let mut rest = range;
+        while let Some(end) = rest.find([']', ')']) {
+            let part = &rest[..=end];
+            rest = rest[end + 1..].trim_start_matches([',', ' ']);
+
+            let lower_inclusive = part.starts_with('[');
+            let upper_inclusive = part.ends_with(']');
+            let body = &part[1..part.len() - 1];
+
+            let interval = match body.split_once(',') {
+                Some((from, to)) => {
+                    let (from, to) = (from.trim(), to.trim());
+                    Interval {
+                        lower: (!from.is_empty()).then(|| Bound {
+                            version: from.to_string(),
+                            inclusive: lower_inclusive,
+                        }),
+                        upper: (!to.is_empty()).then(|| Bound {
+                            version: to.to_string(),
+                            inclusive: upper_inclusive,
+                        }),
+                    }
+                }
+                None => Interval {
+                    lower: Some(Bound {
+                        version: body.trim().to_string(),
+                        inclusive: true,
+                    }),
+                    upper: Some(Bound {
+                        version: body.trim().to_string(),
+                        inclusive: true,
+                    }),
+                },
+            };
+            intervals.push(interval);
+        }
+
+        Self(intervals)
+    }
+
+    // Fabric predicate: ">=2.0", "<3 >=2", "~1.2", "^1.2", "1.2.x", "*"
+    pub fn from_fabric(predicate: &str) -> Self {
+        let mut interval = Interval::default();
+
+        for part in predicate.split_whitespace() {
+            let next = if part == "*" {
+                Interval::default()
+            } else if let Some(v) = part.strip_prefix(">=") {
+                Interval::at_least(v, true)
+            } else if let Some(v) = part.strip_prefix("<=") {
+                Interval::below(v, true)
+            } else if let Some(v) = part.strip_prefix('>') {
+                Interval::at_least(v, false)
+            } else if let Some(v) = part.strip_prefix('<') {
+                Interval::below(v, false)
+            } else if let Some(v) = part.strip_prefix('~') {
+                // Same minor version
+                let mut next: Vec<u64> = v
+                    .split('.')
+                    .take(2)
+                    .filter_map(|p| p.parse().ok())
+                    .collect();
+                next.resize(2, 0);
+                next[1] += 1;
+                Interval::between(v, &format!("{}.{}", next[0], next[1]))
+            } else if let Some(v) = part.strip_prefix('^') {
+                // Same major version
+                let major: u64 = v
+                    .split('.')
+                    .next()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or(0);
+                Interval::between(v, &(major + 1).to_string())
+            } else if let Some(v) = part.strip_suffix(".x") {
+                let mut parts: Vec<String> = v.split('.').map(|p| p.to_string()).collect();
+                let last = parts
+                    .last()
+                    .and_then(|p| p.parse::<u64>().ok())
+                    .unwrap_or(0);
+                let from = v.to_string();
+                if let Some(p) = parts.last_mut() {
+                    *p = (last + 1).to_string();
+                }
+                Interval::between(&from, &parts.join("."))
+            } else {
+                let v = part.trim_start_matches('=');
+                Interval {
+                    lower: Interval::at_least(v, true).lower,
+                    upper: Interval::below(v, true).upper,
+                }
+            };
+            interval = interval.intersect(next);
+        }
+
+        if interval == Interval::default() {
+            return Self::any();
+        }
+
+        Self(vec![interval])
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "*");
+        }
+
+        let parts: Vec<String> = self.0.iter().map(|i| i.to_string()).collect();
+        write!(f, "{}", parts.join(" or "))
+    }
+}
+
+fn manifest_version(manifest: &str) -> Option<String> {
+    manifest
+        .lines()
+        .find_map(|l| l.strip_prefix("Implementation-Version:"))
+        .map(|v| v.trim().to_string())
+}
+
+// Forge META-INF/mods.toml. `jar_version` replaces "${file.jarVersion}".
+pub fn parse_mods_toml(
+    filename: &str,
+    content: &str,
+    jar_version: Option<&str>,
+) -> Result<Vec<ModInfo>, Box<dyn Error>> {
+    let data: toml::Value = toml::from_str(content)?;
+    let mut ret = Vec::new();
+
+    let mods = match data.get("mods").and_then(|m| m.as_array()) {
+        Some(m) => m,
+        None => return Ok(ret),
+    };
+
+    for m in mods {
+        let mod_id = m
+            .get("modId")
+            .and_then(|v| v.as_str())
+            .ok_or("no field: 'modId'")?
+            .to_string();
+        let mut version = m
+            .get("version")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        if version == "${file.jarVersion}" {
+            version = jar_version.unwrap_or_default().to_string();
+        }
+        let display_name = m
+            .get("displayName")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&mod_id)
+            .to_string();
+
+        let dependencies = data
+            .get("dependencies")
+            .and_then(|d| d.get(&mod_id))
+            .and_then(|d| d.as_array())
+            .map(|deps| {
+                deps.iter()
+                    .filter_map(|d| {
+                        let mandatory = d
+                            .get("mandatory")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or_else(|| {
+                                d.get("type").and_then(|v| v.as_str()) == Some("required")
+                            });
+                        Some(Dependency {
+                            mod_id: d.get("modId")?.as_str()?.to_string(),
+                            range: VersionRange::from_maven(
+                                d.get("versionRange")
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or_default(),
+                            ),
+                            mandatory,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ret.push(ModInfo {
+            filename: filename.to_string(),
+            mod_id,
+            version,
+            display_name,
+            dependencies,
+        });
+    }
+
+    Ok(ret)
+}
+
+pub fn parse_fabric_mod_json(filename: &str, content: &str) -> Result<ModInfo, Box<dyn Error>> {
+    let data: Value = serde_json::from_str(content)?;
+
+    let mod_id = data["id"].as_str().ok_or("no field: 'id'")?.to_string();
+    let mut dependencies = Vec::new();
+
+    if let Some(depends) = data["depends"].as_object() {
+        for (id, predicate) in depends {
+            let range = match predicate {
+                Value::String(p) => VersionRange::from_fabric(p),
+                Value::Array(list) => VersionRange(
+                    list.iter()
+                        .filter_map(|p| p.as_str())
+                        .flat_map(|p| VersionRange::from_fabric(p).0)
+                        .collect(),
+                ),
+                _ => VersionRange::any(),
+            };
+            dependencies.push(Dependency {
+                mod_id: id.clone(),
+                range,
+                mandatory: true,
+            });
+        }
+    }
+
+    Ok(ModInfo {
+        filename: filename.to_string(),
+        display_name: data["name"].as_str().unwrap_or(&mod_id).to_string(),
+        version: data["version"].as_str().unwrap_or_default().to_string(),
+        mod_id,
+        dependencies,
+    })
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Option<String> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut data = String::new();
+    entry.read_to_string(&mut data).ok()?;
+    Some(data)
+}
+
+pub fn inspect_jar(path: &Path) -> Result<Vec<ModInfo>, Box<dyn Error>> {
+    let filename = path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    if let Some(content) = read_entry(&mut archive, "META-INF/mods.toml") {
+        let jar_version =
+            read_entry(&mut archive, "META-INF/MANIFEST.MF").and_then(|m| manifest_version(&m));
+        return parse_mods_toml(&filename, &content, jar_version.as_deref());
+    }
+
+    if let Some(content) = read_entry(&mut archive, "fabric.mod.json") {
+        return Ok(vec![parse_fabric_mod_json(&filename, &content)?]);
+    }
+
+    debug!(target: INSPECTOR, "\"{filename}\" has no mod metadata.");
+    Ok(Vec::new())
+}
+
+pub fn inspect_dir(mods_dir: &Path) -> Result<Vec<ModInfo>, Box<dyn Error>> {
+    let mut ret = Vec::new();
+
+    if !mods_dir.exists() {
+        return Ok(ret);
+    }
+
+    for entry in fs::read_dir(mods_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("jar") {
+            continue;
+        }
+
+        match inspect_jar(&path) {
+            Ok(mut mods) => ret.append(&mut mods),
+            Err(e) => warn!(target: INSPECTOR, "Couldn't inspect {:?}. Error: {e}", path),
+        }
+    }
+
+    Ok(ret)
+}
+
+// Versions of game and loader the instance is launched with
+fn builtin_version(mod_id: &str, instance: &Instance) -> Option<String> {
+    match mod_id {
+        "minecraft" => Some(instance.minecraft_version.clone()),
+        "forge" if instance.loader == FORGE => Some(instance.loader_version.clone()),
+        _ => None,
+    }
+}
+
+pub fn check_dependencies(mods: &[ModInfo], instance: &Instance) -> Vec<DependencyIssue> {
+    let mut issues = Vec::new();
+
+    for m in mods {
+        for dep in m.dependencies.iter().filter(|d| d.mandatory) {
+            if dep.mod_id == JAVA || dep.mod_id == FABRIC_LOADER {
+                continue;
+            }
+
+            let found = builtin_version(&dep.mod_id, instance).or_else(|| {
+                mods.iter()
+                    .find(|o| o.mod_id == dep.mod_id)
+                    .map(|o| o.version.clone())
+            });
+
+            match found {
+                None => issues.push(DependencyIssue::Missing {
+                    mod_name: m.display_name.clone(),
+                    dependency: dep.mod_id.clone(),
+                    range: dep.range.clone(),
+                }),
+                // Unknown version couldn't be checked
+                Some(v) if v.is_empty() || v.contains("${") => (),
+                Some(v) if !dep.range.contains(&v) => issues.push(DependencyIssue::Incompatible {
+                    mod_name: m.display_name.clone(),
+                    dependency: dep.mod_id.clone(),
+                    range: dep.range.clone(),
+                    found: v,
+                }),
+                Some(_) => (),
+            }
+        }
+    }
+
+    issues
+}
@@ -0,0 +1,148 @@
+use std::{error::Error, fs, path::Path};
+
+use log::{info, warn};
+
+use super::{
+    instance::Instance,
+    mod_inspector::{inspect_jar, ModInfo},
+};
+
+pub const MOD_MANAGER: &str = "MINECRAFT/MODMANAGER";
+
+pub const DISABLED_EXT: &str = ".disabled";
+
+pub struct InstalledMod {
+    // Jar name without ".disabled"
+    pub filename: String,
+    pub enabled: bool,
+    pub user_added: bool,
+    pub info: Vec<ModInfo>,
+}
+
+impl InstalledMod {
+    pub fn display_name(&self) -> String {
+        match self.info.first() {
+            Some(i) => format!("{} {}", i.display_name, i.version),
+            None => self.filename.clone(),
+        }
+    }
+}
+
+pub fn mods_dir() -> Result<std::path::PathBuf, Box<dyn Error>> {
+    Ok(Instance::game_dir()?.join("mods"))
+}
+
+pub fn list(mods_dir: &Path, instance: &Instance) -> Result<Vec<InstalledMod>, Box<dyn Error>> {
+    let mut ret = Vec::new();
+
+    if !mods_dir.exists() {
+        return Ok(ret);
+    }
+
+    for entry in fs::read_dir(mods_dir)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+
+        let (filename, enabled) = match name.strip_suffix(DISABLED_EXT) {
+            Some(f) => (f.to_string(), false),
+            None => (name.clone(), true),
+        };
+        if !filename.ends_with(".jar") {
+            continue;
+        }
+
+        let info = inspect_jar(&path).unwrap_or_else(|e| {
+            warn!(target: MOD_MANAGER, "Couldn't inspect \"{name}\". Error: {e}");
+            Vec::new()
+        });
+
+        ret.push(InstalledMod {
+            user_added: instance.user_mods.contains(&filename),
+            filename,
+            enabled,
+            info,
+        });
+    }
+
+    ret.sort_by_key(|m| m.filename.to_lowercase());
+
+    Ok(ret)
+}
+
+pub fn set_enabled(mods_dir: &Path, filename: &str, enabled: bool) -> Result<(), Box<dyn Error>> {
+    let jar = mods_dir.join(filename);
+    let disabled = mods_dir.join(format!("{filename}{DISABLED_EXT}"));
+
+    if enabled {
+        fs::rename(disabled, jar)?;
+    } else {
+        fs::rename(jar, disabled)?;
+    }
+
+    info!(target: MOD_MANAGER, "Mod \"{filename}\" enabled: {enabled}");
+
+    Ok(())
+}
+
+// Copy jar into mods dir and mark it as user's, so sync never deletes it. Instance is saved by
+// caller.
+pub fn add(
+    mods_dir: &Path,
+    source: &Path,
+    instance: &mut Instance,
+) -> Result<String, Box<dyn Error>> {
+    let filename = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Wrong mod file name.")?
+        .to_string();
+
+    if !filename.ends_with(".jar") {
+        return Err(format!("\"{filename}\" is not a jar file.").into());
+    }
+
+    // Server mod would be overwritten and then replaced back by next sync
+    if instance.managed_mods.contains(&filename) {
+        return Err(format!("\"{filename}\" is installed by server already.").into());
+    }
+
+    if !mods_dir.exists() {
+        fs::create_dir_all(mods_dir)?;
+    }
+
+    fs::copy(source, mods_dir.join(&filename))?;
+
+    if !instance.user_mods.contains(&filename) {
+        instance.user_mods.push(filename.clone());
+    }
+
+    info!(target: MOD_MANAGER, "User mod \"{filename}\" added.");
+
+    Ok(filename)
+}
+
+// File names in mods dir which belong to user: user mods and disabled mods
+pub fn user_owned_files(
+    mods_dir: &Path,
+    instance: &Instance,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut ret = Vec::new();
+
+    if !mods_dir.exists() {
+        return Ok(ret);
+    }
+
+    for entry in fs::read_dir(mods_dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        let jar = name.strip_suffix(DISABLED_EXT).unwrap_or(&name);
+
+        if name.ends_with(DISABLED_EXT) || instance.user_mods.iter().any(|m| m == jar) {
+            ret.push(name);
+        }
+    }
+
+    Ok(ret)
+}
@@ -0,0 +1,653 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info, warn};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    launcher::commands::{Command, DownloadProgress, Phase},
+    utils::{
+        archive::{recover, safe_join, swap},
+        constants::{GET_CURSEFORGE_FILE, URL, WORKING_DIR},
+        download::SpeedMeter,
+        hash::{sha1_file, sha256_file, sha512_file},
+        http::client_builder,
+    },
+};
+
+use super::{
+    downloader::download_file,
+    instance::{Instance, FABRIC, FORGE},
+    manifest::{is_user_path, to_manifest_path, walk, FileManifest, PackageInfo, USER_PATHS},
+    mods::ModManifest,
+};
+
+pub const MODPACK: &str = "MINECRAFT/MODPACK";
+
+const MRPACK_INDEX: &str = "modrinth.index.json";
+const CURSEFORGE_MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Mrpack,
+    // Zip served by GET_MINECRAFT and file manifest next to it
+    ServerPackage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    // Top level files and dirs of game dir
+    pub folders: Vec<String>,
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackFile {
+    // Path in game dir. Ends with "/" if file name is given by metadata.
+    pub path: String,
+    pub urls: Vec<String>,
+    pub sha1: Option<String>,
+    pub size: Option<u64>,
+    // Name, size and hash of file for packs which don't list them
+    pub meta_url: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Modpack {
+    pub name: String,
+    pub minecraft_version: String,
+    pub loader: String,
+    pub loader_version: String,
+    pub files: Vec<PackFile>,
+    // Archive dirs copied into game dir, in order
+    pub overrides: Vec<String>,
+}
+
+pub fn parse_mrpack_index(data: &str) -> Result<Modpack, Box<dyn Error>> {
+    let index: Value = serde_json::from_str(data)?;
+
+    if index["game"].as_str() != Some("minecraft") {
+        return Err("Modpack is not for minecraft.".into());
+    }
+
+    let deps = &index["dependencies"];
+    let minecraft_version = deps["minecraft"]
+        .as_str()
+        .ok_or("no field: 'dependencies.minecraft'")?
+        .to_string();
+    let (loader, loader_version) = if let Some(v) = deps["forge"].as_str() {
+        (FORGE.to_string(), v.to_string())
+    } else if let Some(v) = deps["fabric-loader"].as_str() {
+        (FABRIC.to_string(), v.to_string())
+    } else {
+        (String::new(), String::new())
+    };
+
+    let mut files = Vec::new();
+    for file in index["files"].as_array().ok_or("no field: 'files'")? {
+        if file["env"]["client"].as_str() == Some("unsupported") {
+            continue;
+        }
+
+        files.push(PackFile {
+            path: file["path"]
+                .as_str()
+                .ok_or("no field: 'files.path'")?
+                .to_string(),
+            urls: file["downloads"]
+                .as_array()
+                .map(|d| {
+                    d.iter()
+                        .filter_map(|u| u.as_str())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            sha1: file["hashes"]["sha1"].as_str().map(String::from),
+            size: file["fileSize"].as_u64(),
+            meta_url: None,
+        });
+    }
+
+    Ok(Modpack {
+        name: index["name"].as_str().unwrap_or("Modpack").to_string(),
+        minecraft_version,
+        loader,
+        loader_version,
+        files,
+        overrides: vec!["overrides/".to_string(), "client-overrides/".to_string()],
+    })
+}
+
+pub fn parse_curseforge_manifest(data: &str) -> Result<Modpack, Box<dyn Error>> {
+    let manifest: Value = serde_json::from_str(data)?;
+
+    let minecraft_version = manifest["minecraft"]["version"]
+        .as_str()
+        .ok_or("no field: 'minecraft.version'")?
+        .to_string();
+
+    // Loader id looks like "forge-36.2.34" or "fabric-0.14.21"
+    let loaders = manifest["minecraft"]["modLoaders"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let loader_id = loaders
+        .iter()
+        .find(|l| l["primary"].as_bool().unwrap_or(false))
+        .or(loaders.first())
+        .and_then(|l| l["id"].as_str())
+        .unwrap_or_default();
+    let (loader, loader_version) = match loader_id.split_once('-') {
+        Some(("forge", v)) => (FORGE.to_string(), v.to_string()),
+        Some(("fabric", v)) => (FABRIC.to_string(), v.to_string()),
+        _ => (String::new(), String::new()),
+    };
+
+    let mut files = Vec::new();
+    for file in manifest["files"].as_array().ok_or("no field: 'files'")? {
+        let project = file["projectID"].as_u64().ok_or("no field: 'projectID'")?;
+        let id = file["fileID"].as_u64().ok_or("no field: 'fileID'")?;
+
+        // CurseForge doesn't publish hashes in manifest, they are taken from file metadata
+        files.push(PackFile {
+            path: "mods/".to_string(),
+            urls: vec![format!(
+                "https://www.curseforge.com/api/v1/mods/{project}/files/{id}/download"
+            )],
+            sha1: None,
+            size: None,
+            meta_url: Some(format!("{URL}{GET_CURSEFORGE_FILE}/{project}/files/{id}")),
+        });
+    }
+
+    let overrides = manifest["overrides"].as_str().unwrap_or("overrides");
+
+    Ok(Modpack {
+        name: manifest["name"].as_str().unwrap_or("Modpack").to_string(),
+        minecraft_version,
+        loader,
+        loader_version,
+        files,
+        overrides: vec![format!("{}/", overrides.trim_end_matches('/'))],
+    })
+}
+
+// CurseForge API file response. Hash with algo 1 is sha1.
+pub fn parse_curseforge_file(data: &str, file: &PackFile) -> Result<PackFile, Box<dyn Error>> {
+    let value: Value = serde_json::from_str(data)?;
+    let data = &value["data"];
+
+    let name = data["fileName"]
+        .as_str()
+        .ok_or("no field: 'data.fileName'")?;
+    let sha1 = data["hashes"]
+        .as_array()
+        .and_then(|h| h.iter().find(|h| h["algo"].as_u64() == Some(1)))
+        .and_then(|h| h["value"].as_str())
+        .ok_or_else(|| format!("CurseForge file \"{name}\" has no sha1."))?;
+
+    let mut urls: Vec<String> = data["downloadUrl"]
+        .as_str()
+        .map(String::from)
+        .into_iter()
+        .collect();
+    urls.extend(file.urls.iter().cloned());
+
+    Ok(PackFile {
+        path: format!("{}{name}", file.path),
+        urls,
+        sha1: Some(sha1.to_string()),
+        size: data["fileLength"].as_u64(),
+        meta_url: None,
+    })
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Option<String> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut data = String::new();
+    entry.read_to_string(&mut data).ok()?;
+    Some(data)
+}
+
+pub fn read(archive: &mut ZipArchive<File>) -> Result<Modpack, Box<dyn Error>> {
+    if let Some(data) = read_entry(archive, MRPACK_INDEX) {
+        return parse_mrpack_index(&data);
+    }
+
+    if let Some(data) = read_entry(archive, CURSEFORGE_MANIFEST) {
+        return parse_curseforge_manifest(&data);
+    }
+
+    Err("Unknown modpack format.".into())
+}
+
+fn resolve_pack_file(client: &Client, file: &PackFile) -> Result<PackFile, Box<dyn Error>> {
+    let url = match &file.meta_url {
+        Some(u) => u,
+        None => return Ok(file.clone()),
+    };
+
+    let resp = client.get(url).send()?;
+    if !resp.status().is_success() {
+        return Err(format!("Server error: {}", resp.status()).into());
+    }
+
+    parse_curseforge_file(&resp.text()?, file)
+}
+
+// Every file is checked by hash, file without one is refused
+fn download_pack_file(
+    client: &Client,
+    file: &PackFile,
+    game_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let file = resolve_pack_file(client, file)?;
+    let sha1 = file
+        .sha1
+        .as_deref()
+        .ok_or_else(|| format!("No hash for \"{}\".", file.path))?;
+    let path = safe_join(game_dir, &file.path)?;
+
+    let mut last_error: Box<dyn Error> = "No download urls.".into();
+    for url in &file.urls {
+        match download_file(client, url, &path, Some(sha1), file.size) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                error!(target: MODPACK, "Couldn't download \"{url}\". Error: {e}");
+                last_error = e;
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+fn copy_overrides(
+    archive: &mut ZipArchive<File>,
+    overrides: &[String],
+    game_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    for prefix in overrides {
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let relative = match entry.name().strip_prefix(prefix.as_str()) {
+                Some(r) if !r.is_empty() => r.to_string(),
+                _ => continue,
+            };
+
+            let path = safe_join(game_dir, &relative)?;
+            if entry.is_dir() {
+                fs::create_dir_all(&path)?;
+                continue;
+            }
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            std::io::copy(&mut entry, &mut File::create(&path)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Download pack files and copy overrides into dir
+fn fetch(
+    archive: &mut ZipArchive<File>,
+    pack: &Modpack,
+    dir: &Path,
+    data_sender: &Sender<Command>,
+) -> Result<(), Box<dyn Error>> {
+    let client = client_builder()?.build()?;
+    let mut progress = DownloadProgress {
+        phase: Phase::Modpack,
+        total_files: pack.files.len() as u64,
+        total_bytes: pack.files.iter().filter_map(|f| f.size).sum(),
+        ..Default::default()
+    };
+    let mut meter = SpeedMeter::new(0);
+
+    for file in &pack.files {
+        progress.file = file.path.clone();
+        progress.speed = meter.update(progress.bytes);
+        match data_sender.send(Command::DOWNLOAD(progress.clone())) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!(target: MODPACK, "Error while sending \"DOWNLOAD\" command to control thread.");
+                Err(e)
+            }
+        }?;
+
+        download_pack_file(&client, file, dir)?;
+        progress.files += 1;
+        progress.bytes += file.size.unwrap_or_default();
+    }
+
+    match data_sender.send(Command::UNZIPING(DownloadProgress {
+        phase: Phase::Modpack,
+        ..Default::default()
+    })) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!(target: MODPACK, "Error while sending \"UNZIPING\" command to control thread.");
+            Err(e)
+        }
+    }?;
+    copy_overrides(archive, &pack.overrides, dir)
+}
+
+// Point instance to game version of modpack. Launch settings and mod bookkeeping are kept.
+pub fn switch_instance(instance: &mut Instance, pack: &Modpack) {
+    let version_id = instance.version_id();
+    instance.name = pack.name.clone();
+    instance.minecraft_version = pack.minecraft_version.clone();
+    instance.loader = pack.loader.clone();
+    instance.loader_version = pack.loader_version.clone();
+
+    if instance.version_id() != version_id {
+        info!(
+            target: MODPACK,
+            "Instance switched from {} to {}.",
+            version_id,
+            instance.version_id()
+        );
+    }
+}
+
+// Paths of `old` dir missing in `new` one. Dir missing in `new` is taken whole.
+fn missing_paths(old: &Path, new: &Path, relative: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut ret = Vec::new();
+
+    for entry in fs::read_dir(old.join(relative))? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        let path = format!("{relative}{name}");
+        if relative.is_empty() && is_user_path(&name) {
+            continue;
+        }
+
+        let target = new.join(&path);
+        if !target.exists() {
+            ret.push(path);
+        } else if target.is_dir() && old.join(&path).is_dir() {
+            ret.append(&mut missing_paths(old, new, &format!("{path}/"))?);
+        }
+    }
+
+    Ok(ret)
+}
+
+// Swap fetched files with game dir. Pack files replace game ones, everything else and player's
+// data is moved over from old game dir. Pack mods become user's, so sync never deletes them.
+pub fn merge(
+    staging: &Path,
+    game_dir: &Path,
+    instance: &mut Instance,
+) -> Result<(), Box<dyn Error>> {
+    recover(game_dir, &USER_PATHS)?;
+
+    let mut keep: Vec<String> = USER_PATHS.iter().map(|p| p.to_string()).collect();
+    if game_dir.exists() {
+        keep.append(&mut missing_paths(game_dir, staging, "")?);
+    }
+
+    let mut pack_mods = Vec::new();
+    if staging.join("mods").exists() {
+        for entry in fs::read_dir(staging.join("mods"))? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".jar") && entry.path().is_file() {
+                pack_mods.push(name);
+            }
+        }
+    }
+
+    swap(
+        staging,
+        game_dir,
+        &keep.iter().map(String::as_str).collect::<Vec<_>>(),
+    )?;
+
+    for name in pack_mods {
+        instance.managed_mods.retain(|m| *m != name);
+        if !instance.user_mods.contains(&name) {
+            instance.user_mods.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+// Install .mrpack or CurseForge zip. Instance is switched to game version of modpack. Files are
+// fetched into staging dir which is swapped with game dir at once, so failed import doesn't
+// leave anything behind. Returns game dir.
+pub fn import(path: &Path, data_sender: Sender<Command>) -> Result<PathBuf, Box<dyn Error>> {
+    info!(target: MODPACK, "Importing modpack {:?}...", path);
+
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let pack = read(&mut archive)?;
+
+    let mut instance = Instance::load()?;
+    switch_instance(&mut instance, &pack);
+
+    let game_dir = Instance::game_dir()?;
+    let staging = game_dir.with_file_name(format!("{WORKING_DIR}.import"));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let result = fetch(&mut archive, &pack, &staging, &data_sender)
+        .and_then(|_| merge(&staging, &game_dir, &mut instance));
+    if staging.exists() {
+        if let Err(e) = fs::remove_dir_all(&staging) {
+            warn!(target: MODPACK, "Couldn't remove {:?}. Error: {e}", staging);
+        }
+    }
+    result?;
+    instance.save()?;
+
+    if !game_dir
+        .join("versions")
+        .join(instance.version_id())
+        .exists()
+    {
+        warn!(
+            target: MODPACK,
+            "Game version {} is not installed yet.",
+            instance.version_id()
+        );
+    }
+    info!(target: MODPACK, "Modpack \"{}\" imported to {:?}.", pack.name, game_dir);
+
+    Ok(game_dir)
+}
+
+// Top level entries of game dir which could be exported
+pub fn exportable_folders(game_dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut ret = Vec::new();
+
+    if !game_dir.exists() {
+        return Ok(ret);
+    }
+
+    for entry in fs::read_dir(game_dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if !is_user_path(&name) {
+            ret.push(name);
+        }
+    }
+    ret.sort();
+
+    Ok(ret)
+}
+
+pub fn default_folders(format: ExportFormat, folders: &[String]) -> Vec<String> {
+    match format {
+        ExportFormat::Mrpack => folders
+            .iter()
+            .filter(|f| ["config", "mods", "scripts", "resourcepacks"].contains(&f.as_str()))
+            .cloned()
+            .collect(),
+        ExportFormat::ServerPackage => folders.to_vec(),
+    }
+}
+
+fn add_file(zip: &mut ZipWriter<File>, name: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    #[allow(unused_mut)]
+    let mut options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        options = options.unix_permissions(fs::metadata(path)?.permissions().mode());
+    }
+
+    zip.start_file(name, options)?;
+    std::io::copy(&mut File::open(path)?, zip)?;
+
+    Ok(())
+}
+
+fn selected_files(game_dir: &Path, folders: &[String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+
+    for folder in folders {
+        if is_user_path(folder) {
+            continue;
+        }
+
+        let path = safe_join(game_dir, folder)?;
+        if path.exists() {
+            files.append(&mut walk(game_dir, &path)?);
+        }
+    }
+
+    Ok(files)
+}
+
+fn export_mrpack(
+    instance: &Instance,
+    game_dir: &Path,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    // Mods published by our server are referenced by url, others are copied as overrides
+    let server_mods: HashMap<String, String> = match ModManifest::load() {
+        Ok(m) => m
+            .mods
+            .into_iter()
+            .map(|m| (m.sha1.to_lowercase(), m.url))
+            .collect(),
+        Err(e) => {
+            warn!(target: MODPACK, "Couldn't load mod manifest, all mods will be copied. Error: {e}");
+            HashMap::new()
+        }
+    };
+
+    let mut zip = ZipWriter::new(File::create(&options.output)?);
+    let mut files = Vec::new();
+
+    for file in selected_files(game_dir, &options.folders)? {
+        let path = game_dir.join(&file);
+        let name = to_manifest_path(&file);
+        let sha1 = sha1_file(&path)?;
+
+        match server_mods.get(&sha1) {
+            Some(url) if name.starts_with("mods/") => files.push(json!({
+                "path": name,
+                "hashes": { "sha1": sha1, "sha512": sha512_file(&path)? },
+                "env": { "client": "required", "server": "required" },
+                "downloads": [url],
+                "fileSize": fs::metadata(&path)?.len(),
+            })),
+            _ => add_file(&mut zip, &format!("overrides/{name}"), &path)?,
+        }
+    }
+
+    let mut dependencies = json!({ "minecraft": instance.minecraft_version });
+    match instance.loader.as_str() {
+        FORGE => dependencies["forge"] = json!(instance.loader_version),
+        FABRIC => dependencies["fabric-loader"] = json!(instance.loader_version),
+        _ => (),
+    }
+
+    let index = json!({
+        "formatVersion": 1,
+        "game": "minecraft",
+        "versionId": version(),
+        "name": instance.name,
+        "files": files,
+        "dependencies": dependencies,
+    });
+
+    zip.start_file(MRPACK_INDEX, FileOptions::default())?;
+    serde_json::to_writer_pretty(&mut zip, &index)?;
+    zip.finish()?;
+
+    Ok(())
+}
+
+// Manifest carries package checksum, fresh install refuses package without it
+pub fn export_server_package(
+    game_dir: &Path,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let files = selected_files(game_dir, &options.folders)?;
+    let mut zip = ZipWriter::new(File::create(&options.output)?);
+
+    // Package is extracted into OS data dir
+    for file in &files {
+        add_file(
+            &mut zip,
+            &format!("{}/{}", WORKING_DIR, to_manifest_path(file)),
+            &game_dir.join(file),
+        )?;
+    }
+    zip.finish()?;
+
+    let mut manifest = FileManifest::generate(version(), game_dir, &files)?;
+    manifest.package = Some(PackageInfo {
+        sha256: sha256_file(&options.output)?,
+        size: fs::metadata(&options.output)?.len(),
+    });
+    let manifest_path = options.output.with_extension("manifest.json");
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+
+    info!(target: MODPACK, "File manifest written to {:?}.", manifest_path);
+
+    Ok(())
+}
+
+fn version() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub fn export(options: &ExportOptions) -> Result<(), Box<dyn Error>> {
+    info!(target: MODPACK, "Exporting instance to {:?}...", options.output);
+
+    let instance = Instance::load()?;
+    let game_dir = Instance::game_dir()?;
+
+    match options.format {
+        ExportFormat::Mrpack => export_mrpack(&instance, &game_dir, options)?,
+        ExportFormat::ServerPackage => export_server_package(&game_dir, options)?,
+    }
+
+    info!(target: MODPACK, "Instance exported.");
+
+    Ok(())
+}
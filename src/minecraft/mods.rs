@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    time::Duration,
+};
+
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    launcher::commands::{Command, DownloadProgress, Phase},
+    utils::{
+        archive::safe_join,
+        constants::{GET_MODS_MANIFEST, URL},
+        download::CancelToken,
+        hash::sha1_file,
+        http::client_builder,
+    },
+};
+
+use super::{
+    engine::{DownloadEngine, EngineOptions, Task},
+    instance::Instance,
+    mod_manager::DISABLED_EXT,
+};
+
+pub const MODS: &str = "MINECRAFT/MODS";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModEntry {
+    pub filename: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+    #[serde(default = "default_required")]
+    pub required: bool,
+    // Filenames of mods which couldn't be installed together with this one
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+}
+
+impl ModEntry {
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() {
+            &self.filename
+        } else {
+            &self.name
+        }
+    }
+
+    pub fn is_selected(&self, choices: &HashMap<String, bool>) -> bool {
+        self.required || choices.get(&self.filename).copied().unwrap_or(false)
+    }
+}
+
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModManifest {
+    pub mods: Vec<ModEntry>,
+}
+
+impl ModManifest {
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let client = client_builder()?
+            .timeout(Duration::from_millis(1500))
+            .build()?;
+
+        let resp = match client.get(format!("{}{}", URL, GET_MODS_MANIFEST)).send() {
+            Ok(r) => Ok(r),
+            Err(e) => {
+                error!(target: MODS, "Error while requesting mod manifest. Error: {e}");
+                Err(e)
+            }
+        }?;
+
+        if !resp.status().is_success() {
+            return Err(format!("Server error: {}", resp.status()).into());
+        }
+
+        Ok(serde_json::from_str(&resp.text()?)?)
+    }
+
+    // Required mods and optional mods chosen by user
+    pub fn selected(&self, choices: &HashMap<String, bool>) -> Vec<ModEntry> {
+        self.mods
+            .iter()
+            .filter(|m| m.is_selected(choices))
+            .cloned()
+            .collect()
+    }
+
+    // Pairs of selected mods declared by server as incompatible
+    pub fn conflicts(&self, choices: &HashMap<String, bool>) -> Vec<(String, String)> {
+        let selected = self.selected(choices);
+        let mut conflicts = Vec::new();
+
+        for entry in &selected {
+            for other in &selected {
+                let declared = entry.conflicts.contains(&other.filename)
+                    || other.conflicts.contains(&entry.filename);
+                if declared && entry.filename < other.filename {
+                    conflicts.push((
+                        entry.display_name().to_string(),
+                        other.display_name().to_string(),
+                    ));
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ModsDiff {
+    pub download: Vec<ModEntry>,
+    pub remove: Vec<String>,
+}
+
+impl ModsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.download.is_empty() && self.remove.is_empty()
+    }
+}
+
+fn is_actual(entry: &ModEntry, path: &Path) -> Result<bool, Box<dyn Error>> {
+    if !path.exists() || fs::metadata(path)?.len() != entry.size {
+        return Ok(false);
+    }
+
+    Ok(sha1_file(path)?.eq_ignore_ascii_case(&entry.sha1))
+}
+
+// Mod file names come from server, so only plain names inside mods dir are accepted
+pub fn mod_path(mods_dir: &Path, filename: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if filename.contains(['/', '\\']) || filename.starts_with('.') {
+        return Err(format!("Wrong mod file name: \"{filename}\"").into());
+    }
+
+    safe_join(mods_dir, filename)
+}
+
+// Compare wanted mods with mods dir. Only previously managed and not user's mods could be
+// removed. Optional mods disabled by user are kept disabled.
+pub fn diff(
+    mods: &[ModEntry],
+    mods_dir: &Path,
+    managed: &[String],
+    user_mods: &[String],
+) -> Result<ModsDiff, Box<dyn Error>> {
+    let mut diff = ModsDiff::default();
+
+    for entry in mods {
+        let path = mod_path(mods_dir, &entry.filename)?;
+        let disabled = mod_path(mods_dir, &format!("{}{DISABLED_EXT}", entry.filename))?;
+        if !entry.required && disabled.exists() {
+            continue;
+        }
+
+        if !is_actual(entry, &path)? {
+            diff.download.push(entry.clone());
+        }
+    }
+
+    for filename in managed {
+        let listed = mods.iter().any(|m| &m.filename == filename);
+        if !listed && !user_mods.contains(filename) && mod_path(mods_dir, filename)?.exists() {
+            diff.remove.push(filename.clone());
+        }
+    }
+
+    Ok(diff)
+}
+
+pub fn sync_mods(data_sender: Sender<Command>, cancel: &CancelToken) -> Result<(), Box<dyn Error>> {
+    info!(target: MODS, "Synchronizing mods...");
+
+    let manifest = ModManifest::load()?;
+    let mut instance = Instance::load()?;
+    let mods_dir = Instance::game_dir()?.join("mods");
+
+    if !mods_dir.exists() {
+        fs::create_dir_all(&mods_dir)?;
+    }
+
+    let mods = manifest.selected(&instance.optional_mods);
+
+    for (a, b) in manifest.conflicts(&instance.optional_mods) {
+        warn!(target: MODS, "Mod \"{a}\" conflicts with \"{b}\".");
+    }
+
+    let diff = diff(
+        &mods,
+        &mods_dir,
+        &instance.managed_mods,
+        &instance.user_mods,
+    )?;
+    debug!(target: MODS, "Download: {} | Remove: {}", diff.download.len(), diff.remove.len());
+
+    let mut tasks = Vec::new();
+    for entry in &diff.download {
+        info!(target: MODS, "Downloading mod \"{}\"...", entry.filename);
+        tasks.push(Task {
+            urls: vec![entry.url.clone()],
+            path: mod_path(&mods_dir, &entry.filename)?,
+            sha1: Some(entry.sha1.clone()),
+            size: Some(entry.size),
+        });
+    }
+
+    let engine = DownloadEngine::new(EngineOptions {
+        cancel: cancel.clone(),
+        ..Default::default()
+    })?;
+    engine.run(&tasks, |progress| {
+        match data_sender.send(Command::DOWNLOAD(DownloadProgress {
+            phase: Phase::Mods,
+            ..progress
+        })) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!(target: MODS, "Error while sending \"DOWNLOAD\" command to control thread.");
+                Err(e.into())
+            }
+        }
+    })?;
+
+    for filename in &diff.remove {
+        info!(target: MODS, "Removing mod \"{filename}\"...");
+        fs::remove_file(mod_path(&mods_dir, filename)?)?;
+    }
+
+    instance.managed_mods = mods.iter().map(|m| m.filename.clone()).collect();
+    instance.save()?;
+
+    info!(target: MODS, "Mods synchronized.");
+
+    Ok(())
+}
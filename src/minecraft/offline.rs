@@ -0,0 +1,134 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::session::Session,
+    utils::{archive::safe_join, constants::LAUNCHER_DIR, hash_cache::HashCache},
+};
+
+use super::manifest::{is_user_path, FileManifest, Policy};
+
+pub const OFFLINE: &str = "MINECRAFT/OFFLINE";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatedFile {
+    pub path: String,
+    pub sha1: String,
+    pub size: u64,
+}
+
+// Game dir state at the last successful validation. Lets the game start when update server
+// is unreachable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastValidation {
+    pub version: String,
+    pub files: Vec<ValidatedFile>,
+}
+
+impl LastValidation {
+    fn path() -> Result<PathBuf, Box<dyn Error>> {
+        let path = dirs::data_dir()
+            .ok_or("OS data dir not found.")?
+            .join(LAUNCHER_DIR);
+
+        if !path.exists() {
+            fs::create_dir_all(&path)?;
+        }
+
+        Ok(path.join("last_validation.json"))
+    }
+
+    // Remember files of valid game dir. Files which user may change are not recorded.
+    pub fn record(manifest: &FileManifest, game_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut files = Vec::new();
+
+        for entry in &manifest.files {
+            if entry.policy != Policy::Always || is_user_path(&entry.path) {
+                continue;
+            }
+
+            if !safe_join(game_dir, &entry.path)?.is_file() {
+                return Err(format!("Validated file \"{}\" is missing.", entry.path).into());
+            }
+            files.push(ValidatedFile {
+                path: entry.path.clone(),
+                sha1: entry.sha1.clone(),
+                size: entry.size,
+            });
+        }
+
+        Ok(Self {
+            version: manifest.version.clone(),
+            files,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::write(Self::path()?, serde_json::to_string(self)?)?;
+        debug!(target: OFFLINE, "Saved validation of {}.", self.version);
+        Ok(())
+    }
+
+    pub fn load() -> Option<Self> {
+        let data = fs::read_to_string(Self::path().ok()?).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    // Files changed since validation. Hashes of unchanged files are taken from cache.
+    pub fn check(
+        &self,
+        game_dir: &Path,
+        cache: &mut HashCache,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut changed = Vec::new();
+        let mut check = Vec::new();
+
+        for file in &self.files {
+            let path = safe_join(game_dir, &file.path)?;
+            let meta = match fs::metadata(&path) {
+                Ok(m) if m.is_file() => m,
+                _ => {
+                    warn!(target: OFFLINE, "Missing: {}", file.path);
+                    changed.push(file.path.clone());
+                    continue;
+                }
+            };
+
+            if meta.len() != file.size {
+                warn!(target: OFFLINE, "Modified: {}", file.path);
+                changed.push(file.path.clone());
+                continue;
+            }
+            check.push((file, path));
+        }
+
+        let paths: Vec<PathBuf> = check.iter().map(|(_, p)| p.clone()).collect();
+        for ((file, _), sha1) in check.iter().zip(cache.sha1_files(&paths)?) {
+            if !sha1.eq_ignore_ascii_case(&file.sha1) {
+                warn!(target: OFFLINE, "Modified: {}", file.path);
+                changed.push(file.path.clone());
+            }
+        }
+        changed.sort();
+
+        info!(
+            target: OFFLINE,
+            "Last validated version {} | Changed: {}",
+            self.version,
+            changed.len()
+        );
+
+        Ok(changed)
+    }
+}
+
+// Game could be started without update server
+pub fn offline_available() -> bool {
+    LastValidation::load().is_some() && Session::cached().is_some()
+}
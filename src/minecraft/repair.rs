@@ -0,0 +1,123 @@
+use std::{error::Error, fs, path::Path, sync::mpsc::Sender};
+
+use log::{info, warn};
+
+use crate::{launcher::commands::Command, utils::download::CancelToken};
+
+use super::{
+    instance::Instance, manifest::to_manifest_path, offline::LastValidation, updater::update_game,
+    validate::validate_files,
+};
+
+pub const REPAIR: &str = "MINECRAFT/REPAIR";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RepairReport {
+    pub version: String,
+    pub checked: usize,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    // Cache dirs and files removed before check, relative to game dir
+    pub cleared: Vec<String>,
+}
+
+impl RepairReport {
+    pub fn summary(&self) -> String {
+        let mut ret = format!(
+            "Game {} repaired. Checked {} files.",
+            self.version, self.checked
+        );
+
+        if self.missing.is_empty() && self.modified.is_empty() {
+            ret.push_str("\nAll files are valid.");
+        }
+        if !self.missing.is_empty() {
+            ret.push_str(&format!(
+                "\nRestored {} missing:\n{}",
+                self.missing.len(),
+                self.missing.join("\n")
+            ));
+        }
+        if !self.modified.is_empty() {
+            ret.push_str(&format!(
+                "\nReplaced {} broken:\n{}",
+                self.modified.len(),
+                self.modified.join("\n")
+            ));
+        }
+        if !self.cleared.is_empty() {
+            ret.push_str(&format!("\nCleared caches: {}", self.cleared.join(", ")));
+        }
+
+        ret
+    }
+}
+
+// Remove extracted natives of every version and leftovers of forge processors in libraries.
+// Natives listed in manifest are downloaded again as missing files.
+pub fn clear_caches(game_dir: &Path, extra: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut cleared = Vec::new();
+
+    let versions = game_dir.join("versions");
+    if versions.is_dir() {
+        for entry in fs::read_dir(&versions)? {
+            let natives = entry?.path().join("natives");
+            if natives.is_dir() {
+                fs::remove_dir_all(&natives)?;
+                cleared.push(to_manifest_path(natives.strip_prefix(game_dir)?));
+            }
+        }
+    }
+
+    for path in extra.iter().filter(|p| p.starts_with("libraries/")) {
+        fs::remove_file(game_dir.join(path))?;
+        cleared.push(path.clone());
+    }
+
+    cleared.sort();
+    for path in &cleared {
+        info!(target: REPAIR, "Cleared \"{path}\".");
+    }
+
+    Ok(cleared)
+}
+
+// Check every managed file by hash, ignoring hash cache, and download only broken ones
+pub fn repair_game(
+    data_sender: Sender<Command>,
+    cancel: &CancelToken,
+) -> Result<RepairReport, Box<dyn Error>> {
+    let game_dir = Instance::game_dir()?;
+    info!(target: REPAIR, "Repairing game...");
+
+    // Processor leftovers are found as extra files, so game dir is checked twice
+    let (_, diff) = validate_files(true)?;
+    let cleared = clear_caches(&game_dir, &diff.extra)?;
+    let (manifest, diff) = validate_files(false)?;
+
+    let report = RepairReport {
+        version: manifest.version.clone(),
+        checked: manifest.files.len(),
+        missing: diff.missing.iter().map(|f| f.path.clone()).collect(),
+        modified: diff.modified.iter().map(|f| f.path.clone()).collect(),
+        cleared,
+    };
+    if !diff.is_valid() {
+        update_game(data_sender, &manifest, &diff, cancel)?;
+    }
+
+    match LastValidation::record(&manifest, &game_dir).and_then(|v| v.save()) {
+        Ok(_) => (),
+        Err(e) => warn!(target: REPAIR, "Couldn't record validation. Error: {e}"),
+    }
+
+    info!(
+        target: REPAIR,
+        "Repaired | Missing: {} | Modified: {} | Cleared: {}",
+        report.missing.len(),
+        report.modified.len(),
+        report.cleared.len()
+    );
+
+    Ok(report)
+}
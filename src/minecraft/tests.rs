@@ -1,6 +1,42 @@
-use std::fs;
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, Instant},
+};
 
-use crate::minecraft::minecraft_json::{Library, MinecraftJson};
+use crate::{
+    api::session::Session,
+    utils::{
+        constants::{GET_MINECRAFT_FILES, MIRRORS},
+        download::{
+            is_cancelled, CancelToken, Checksum, ChecksumMismatch, DownloadOptions, RetryPolicy,
+        },
+        hash::{hex, sha256_file},
+        hash_cache::HashCache,
+        test_helpers::{dead_url, serve_files, served_body, TempDir},
+    },
+};
+
+use super::{
+    authlib::{check_jar, AuthlibInjector},
+    downloader::{download_package, keep_paths, package_size},
+    engine::{DownloadEngine, EngineOptions, Task},
+    instance::{Instance, FABRIC},
+    manifest::{walk, FileEntry, FileManifest, PackageInfo, Policy},
+    minecraft_json::{Library, MinecraftJson},
+    mod_inspector::{check_dependencies, parse_fabric_mod_json, parse_mods_toml, VersionRange},
+    mod_manager::{add, set_enabled, user_owned_files},
+    modpack::{
+        default_folders, export_server_package, exportable_folders, merge, parse_curseforge_file,
+        parse_curseforge_manifest, parse_mrpack_index, switch_instance, ExportFormat,
+        ExportOptions, Modpack,
+    },
+    mods::{diff, mod_path, ModEntry, ModManifest},
+    offline::LastValidation,
+    repair::{clear_caches, RepairReport},
+    updater::obsolete,
+    GameOptions,
+};
 
 #[test]
 fn load_forge_json_test() {
@@ -41,3 +77,886 @@ fn jvm_args_to_arg_test() {
     let mr = MinecraftJson::new(&data).unwrap();
     println!("{:?}", mr.jvm_args_to_arg());
 }
+
+#[test]
+fn game_options_session_test() {
+    let session = Session {
+        username: "Steve".to_string(),
+        uuid: "069a79f444e94726a5befca90e38aaf5".to_string(),
+        access_token: "token".to_string(),
+        user_type: "mojang".to_string(),
+    };
+    let mut options = GameOptions::default();
+    options.set_session(&session);
+
+    let args = options.to_args();
+    let value_of = |key: &str| {
+        let i = args.iter().position(|a| a == key).unwrap();
+        args[i + 1].clone()
+    };
+
+    assert_eq!(value_of("--username"), "Steve");
+    assert_eq!(value_of("--uuid"), "069a79f444e94726a5befca90e38aaf5");
+    assert_eq!(value_of("--accessToken"), "token");
+    assert_eq!(value_of("--userType"), "mojang");
+}
+
+#[test]
+fn mods_diff_test() {
+    let dir = TempDir::new("mods_diff_test");
+
+    // sha1("actual")
+    fs::write(dir.join("actual.jar"), "actual").unwrap();
+    fs::write(dir.join("changed.jar"), "changed").unwrap();
+    fs::write(dir.join("obsolete.jar"), "obsolete").unwrap();
+    fs::write(dir.join("user.jar"), "user").unwrap();
+    fs::write(dir.join("optional.jar.disabled"), "optional").unwrap();
+
+    let entry = |filename: &str, sha1: &str, size: u64| ModEntry {
+        filename: filename.to_string(),
+        sha1: sha1.to_string(),
+        size,
+        required: true,
+        ..Default::default()
+    };
+    let manifest = ModManifest {
+        mods: vec![
+            entry("actual.jar", "50f46198a1bd51840547aee34fc045921e43d4c6", 6),
+            entry("changed.jar", "0000000000000000000000000000000000000000", 7),
+            entry("missing.jar", "0000000000000000000000000000000000000000", 1),
+            ModEntry {
+                filename: "optional.jar".to_string(),
+                required: false,
+                ..Default::default()
+            },
+        ],
+    };
+    let managed = vec![
+        "actual.jar".to_string(),
+        "changed.jar".to_string(),
+        "obsolete.jar".to_string(),
+        "user.jar".to_string(),
+    ];
+    let user_mods = vec!["user.jar".to_string()];
+
+    let diff = diff(&manifest.mods, &dir, &managed, &user_mods).unwrap();
+    let download: Vec<&str> = diff.download.iter().map(|m| m.filename.as_str()).collect();
+
+    assert_eq!(download, vec!["changed.jar", "missing.jar"]);
+    assert_eq!(diff.remove, vec!["obsolete.jar".to_string()]);
+
+    // Names from server couldn't point outside of mods dir
+    for name in ["../options.txt", "/etc/passwd", "..", "sub\\..\\..\\a.jar"] {
+        assert!(mod_path(&dir, name).is_err(), "{name}");
+        let bad = vec![entry(name, "0000000000000000000000000000000000000000", 1)];
+        assert!(super::mods::diff(&bad, &dir, &[], &[]).is_err());
+        assert!(super::mods::diff(&[], &dir, &[name.to_string()], &[]).is_err());
+    }
+    assert_eq!(mod_path(&dir, "jei.jar").unwrap(), dir.join("jei.jar"));
+}
+
+#[test]
+fn optional_mods_test() {
+    let entry = |filename: &str, required: bool, conflicts: Vec<&str>| ModEntry {
+        filename: filename.to_string(),
+        required,
+        conflicts: conflicts.iter().map(|c| c.to_string()).collect(),
+        ..Default::default()
+    };
+    let manifest = ModManifest {
+        mods: vec![
+            entry("forge-core.jar", true, vec![]),
+            entry("minimap.jar", false, vec!["worldmap.jar"]),
+            entry("worldmap.jar", false, vec![]),
+            entry("shaders.jar", false, vec![]),
+        ],
+    };
+
+    let mut choices = HashMap::new();
+    choices.insert("minimap.jar".to_string(), true);
+    choices.insert("shaders.jar".to_string(), false);
+
+    let selected: Vec<String> = manifest
+        .selected(&choices)
+        .into_iter()
+        .map(|m| m.filename)
+        .collect();
+    assert_eq!(selected, vec!["forge-core.jar", "minimap.jar"]);
+    assert!(manifest.conflicts(&choices).is_empty());
+
+    choices.insert("worldmap.jar".to_string(), true);
+    assert_eq!(
+        manifest.conflicts(&choices),
+        vec![("minimap.jar".to_string(), "worldmap.jar".to_string())]
+    );
+}
+
+#[test]
+fn version_range_test() {
+    let range = VersionRange::from_maven("[2.0,)");
+    assert!(range.contains("2.0"));
+    assert!(range.contains("2.10.1"));
+    assert!(!range.contains("1.9"));
+    assert_eq!(range.to_string(), ">= 2.0");
+
+    let range = VersionRange::from_maven("[1.16.5,1.17)");
+    assert!(range.contains("1.16.5"));
+    assert!(!range.contains("1.17"));
+    assert_eq!(range.to_string(), ">= 1.16.5 and < 1.17");
+
+    assert!(VersionRange::from_maven("1.0").contains("0.1"));
+    assert!(VersionRange::from_maven("[1.0]").contains("1.0.0"));
+    assert!(!VersionRange::from_maven("(,1.0),(1.0,)").contains("1.0"));
+
+    assert!(VersionRange::from_fabric(">=0.4.0 <0.5").contains("0.4.8"));
+    assert!(!VersionRange::from_fabric(">=0.4.0 <0.5").contains("0.5.0"));
+    assert!(VersionRange::from_fabric("~1.2.3").contains("1.2.9"));
+    assert!(!VersionRange::from_fabric("^1.2").contains("2.0"));
+    assert!(VersionRange::from_fabric("1.16.x").contains("1.16.5"));
+    assert!(VersionRange::from_fabric("*").contains("anything"));
+}
+
+#[test]
+fn mod_dependencies_test() {
+    let toml = r#"
+modLoader="javafml"
+loaderVersion="[36,)"
+
+[[mods]]
+modId="minimap"
+version="${file.jarVersion}"
+displayName="Minimap"
+
+[[dependencies.minimap]]
+    modId="forge"
+    mandatory=true
+    versionRange="[36,)"
+[[dependencies.minimap]]
+    modId="corelib"
+    mandatory=true
+    versionRange="[2.0,)"
+[[dependencies.minimap]]
+    modId="jei"
+    mandatory=false
+    versionRange="[7.0,)"
+"#;
+    let minimap = parse_mods_toml("minimap.jar", toml, Some("1.4.2")).unwrap();
+    assert_eq!(minimap[0].version, "1.4.2");
+
+    let mut corelib =
+        parse_fabric_mod_json("corelib.jar", r#"{"id": "corelib", "version": "1.9.1"}"#).unwrap();
+
+    let instance = Instance {
+        minecraft_version: "1.16.5".to_string(),
+        loader: "forge".to_string(),
+        loader_version: "36.2.34".to_string(),
+        ..Default::default()
+    };
+    let issues = check_dependencies(&minimap, &instance);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(
+        issues[0].to_string(),
+        "mod Minimap needs corelib >= 2.0, but it's missing"
+    );
+
+    let mut mods = minimap.clone();
+    mods.push(corelib.clone());
+    let issues = check_dependencies(&mods, &instance);
+    assert_eq!(
+        issues[0].to_string(),
+        "mod Minimap needs corelib >= 2.0, found 1.9.1"
+    );
+
+    corelib.version = "2.1.0".to_string();
+    mods.pop();
+    mods.push(corelib);
+    assert!(check_dependencies(&mods, &instance).is_empty());
+
+    // Game and loader versions come from instance
+    let old_forge = Instance {
+        loader_version: "35.1.4".to_string(),
+        ..instance
+    };
+    assert_eq!(
+        check_dependencies(&mods, &old_forge)[0].to_string(),
+        "mod Minimap needs forge >= 36, found 35.1.4"
+    );
+    let fabric = Instance {
+        loader: "fabric-loader".to_string(),
+        ..old_forge
+    };
+    assert_eq!(
+        check_dependencies(&mods, &fabric)[0].to_string(),
+        "mod Minimap needs forge >= 36, but it's missing"
+    );
+}
+
+#[test]
+fn modpack_parse_test() {
+    let index = r#"{
+        "formatVersion": 1,
+        "game": "minecraft",
+        "versionId": "1.0.0",
+        "name": "Funny Pack",
+        "files": [
+            {
+                "path": "mods/jei.jar",
+                "hashes": { "sha1": "abc", "sha512": "def" },
+                "env": { "client": "required", "server": "required" },
+                "downloads": ["https://cdn.modrinth.com/jei.jar"],
+                "fileSize": 100
+            },
+            {
+                "path": "mods/server-only.jar",
+                "hashes": { "sha1": "abc" },
+                "env": { "client": "unsupported", "server": "required" },
+                "downloads": [],
+                "fileSize": 1
+            }
+        ],
+        "dependencies": { "minecraft": "1.16.5", "forge": "36.2.34" }
+    }"#;
+    let pack = parse_mrpack_index(index).unwrap();
+    assert_eq!(pack.name, "Funny Pack");
+    assert_eq!(pack.minecraft_version, "1.16.5");
+    assert_eq!(pack.loader_version, "36.2.34");
+    assert_eq!(pack.files.len(), 1);
+    assert_eq!(pack.files[0].sha1.as_deref(), Some("abc"));
+    assert_eq!(pack.files[0].size, Some(100));
+
+    let manifest = r#"{
+        "minecraft": {
+            "version": "1.16.5",
+            "modLoaders": [{ "id": "forge-36.2.34", "primary": true }]
+        },
+        "manifestType": "minecraftModpack",
+        "manifestVersion": 1,
+        "name": "Curse Pack",
+        "files": [{ "projectID": 238222, "fileID": 3438494, "required": true }],
+        "overrides": "overrides"
+    }"#;
+    let pack = parse_curseforge_manifest(manifest).unwrap();
+    assert_eq!(pack.loader_version, "36.2.34");
+    assert_eq!(pack.files[0].path, "mods/");
+    assert_eq!(pack.overrides, vec!["overrides/".to_string()]);
+    assert!(pack.files[0]
+        .meta_url
+        .as_deref()
+        .unwrap()
+        .ends_with("/238222/files/3438494"));
+
+    let meta = r#"{ "data": {
+        "fileName": "jei-1.16.5.jar",
+        "fileLength": 1000,
+        "downloadUrl": "https://edge.forgecdn.net/files/3438/494/jei-1.16.5.jar",
+        "hashes": [{ "value": "md5", "algo": 2 }, { "value": "abc", "algo": 1 }]
+    } }"#;
+    let file = parse_curseforge_file(meta, &pack.files[0]).unwrap();
+    assert_eq!(file.path, "mods/jei-1.16.5.jar");
+    assert_eq!(file.sha1.as_deref(), Some("abc"));
+    assert_eq!(file.size, Some(1000));
+    assert_eq!(file.urls.len(), 2);
+    assert!(file.urls[0].starts_with("https://edge.forgecdn.net/"));
+    // File without sha1 couldn't be checked
+    let meta = r#"{ "data": { "fileName": "a.jar", "hashes": [{ "value": "md5", "algo": 2 }] } }"#;
+    assert!(parse_curseforge_file(meta, &pack.files[0]).is_err());
+}
+
+#[test]
+fn modpack_merge_test() {
+    let dir = TempDir::new("modpack_merge_test");
+    let (staging, game_dir) = (dir.join("staging"), dir.join("game"));
+    fs::create_dir_all(staging.join("mods")).unwrap();
+    fs::create_dir_all(staging.join("config")).unwrap();
+    fs::create_dir_all(game_dir.join("config")).unwrap();
+    fs::write(staging.join("mods/jei.jar"), "new jei").unwrap();
+    fs::write(staging.join("config/jei.toml"), "new").unwrap();
+    fs::write(game_dir.join("config/jei.toml"), "old").unwrap();
+    fs::write(game_dir.join("config/other.toml"), "other").unwrap();
+    fs::create_dir_all(game_dir.join("versions/1.16.5")).unwrap();
+    fs::write(game_dir.join("versions/1.16.5/1.16.5.json"), "{}").unwrap();
+    fs::create_dir_all(game_dir.join("mods")).unwrap();
+    fs::write(game_dir.join("mods/user.jar"), "user").unwrap();
+    fs::write(game_dir.join("options.txt"), "player").unwrap();
+    fs::write(staging.join("options.txt"), "pack").unwrap();
+
+    let mut instance = Instance {
+        managed_mods: vec!["jei.jar".to_string()],
+        ..Default::default()
+    };
+    merge(&staging, &game_dir, &mut instance).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(game_dir.join("mods/jei.jar")).unwrap(),
+        "new jei"
+    );
+    assert_eq!(
+        fs::read_to_string(game_dir.join("config/jei.toml")).unwrap(),
+        "new"
+    );
+    assert!(game_dir.join("config/other.toml").exists());
+    assert!(game_dir.join("versions/1.16.5/1.16.5.json").exists());
+    assert!(game_dir.join("mods/user.jar").exists());
+    assert_eq!(
+        fs::read_to_string(game_dir.join("options.txt")).unwrap(),
+        "player"
+    );
+    assert!(!staging.exists());
+    assert!(!dir.join("game.old").exists());
+    // Pack mods are not removed by sync
+    assert_eq!(instance.user_mods, vec!["jei.jar".to_string()]);
+    assert!(instance.managed_mods.is_empty());
+}
+
+#[test]
+fn switch_instance_test() {
+    let pack = Modpack {
+        name: "Pack".to_string(),
+        minecraft_version: "1.20.1".to_string(),
+        loader: FABRIC.to_string(),
+        loader_version: "0.14.21".to_string(),
+        ..Default::default()
+    };
+    let mut instance = Instance {
+        user_mods: vec!["user.jar".to_string()],
+        ..Default::default()
+    };
+    switch_instance(&mut instance, &pack);
+
+    assert_eq!(instance.name, "Pack");
+    assert_eq!(instance.version_id(), "fabric-loader-0.14.21-1.20.1");
+    assert_eq!(instance.user_mods, vec!["user.jar".to_string()]);
+}
+
+#[test]
+fn authlib_injector_test() {
+    let dir = TempDir::new("authlib_injector_test");
+    let jar = dir.join("authlib-injector.jar");
+    // Nothing listens on port 1, so update fails
+    let meta = "http://127.0.0.1:1/artifact/latest.json";
+    let yggdrasil = "http://127.0.0.1:1/api/yggdrasil";
+
+    assert!(AuthlibInjector::prepare_at(&jar, meta, yggdrasil).is_err());
+
+    fs::write(&jar, "agent").unwrap();
+    let sha256 = "d4f0bc5a29de06b510f9aa428f1eedba926012b591fef7a518e776a7c9bd1824";
+    assert!(check_jar(&jar, sha256).is_ok());
+    assert!(check_jar(&jar, &sha256.replace('d', "e")).is_err());
+
+    // Jar without saved metadata is not enough for launch
+    assert!(AuthlibInjector::prepare_at(&jar, meta, yggdrasil).is_err());
+
+    fs::write(dir.join("yggdrasil.prefetched"), "e30=").unwrap();
+    let injector = AuthlibInjector::prepare_at(&jar, meta, yggdrasil).unwrap();
+    assert_eq!(
+        injector.to_args(),
+        vec![
+            format!("-javaagent:{}={yggdrasil}", jar.to_string_lossy()),
+            "-Dauthlibinjector.yggdrasil.prefetched=e30=".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn mod_manager_test() {
+    let dir = TempDir::new("mod_manager_test");
+    let mods_dir = dir.join("mods");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("jei.jar"), "jei").unwrap();
+    fs::write(dir.join("forge.jar"), "user forge").unwrap();
+    fs::write(dir.join("readme.txt"), "readme").unwrap();
+
+    let mut instance = Instance {
+        managed_mods: vec!["forge.jar".to_string()],
+        ..Default::default()
+    };
+    assert!(user_owned_files(&mods_dir, &instance).unwrap().is_empty());
+    assert!(add(&mods_dir, &dir.join("readme.txt"), &mut instance).is_err());
+
+    assert_eq!(
+        add(&mods_dir, &dir.join("jei.jar"), &mut instance).unwrap(),
+        "jei.jar"
+    );
+    assert_eq!(fs::read_to_string(mods_dir.join("jei.jar")).unwrap(), "jei");
+    assert_eq!(instance.user_mods, vec!["jei.jar".to_string()]);
+    // Server mod with the same name is not replaced
+    assert!(add(&mods_dir, &dir.join("forge.jar"), &mut instance).is_err());
+    assert!(!mods_dir.join("forge.jar").exists());
+    assert_eq!(instance.managed_mods, vec!["forge.jar".to_string()]);
+    // Adding the same mod again doesn't duplicate it
+    add(&mods_dir, &dir.join("jei.jar"), &mut instance).unwrap();
+    assert_eq!(instance.user_mods.len(), 1);
+
+    fs::write(mods_dir.join("forge.jar"), "forge").unwrap();
+    set_enabled(&mods_dir, "forge.jar", false).unwrap();
+    assert!(!mods_dir.join("forge.jar").exists());
+    assert!(mods_dir.join("forge.jar.disabled").exists());
+
+    let mut owned = user_owned_files(&mods_dir, &instance).unwrap();
+    owned.sort();
+    assert_eq!(
+        owned,
+        vec!["forge.jar.disabled".to_string(), "jei.jar".to_string()]
+    );
+
+    set_enabled(&mods_dir, "forge.jar", true).unwrap();
+    assert!(mods_dir.join("forge.jar").exists());
+    assert_eq!(
+        user_owned_files(&mods_dir, &instance).unwrap(),
+        vec!["jei.jar".to_string()]
+    );
+    assert!(set_enabled(&mods_dir, "missing.jar", true).is_err());
+}
+
+#[test]
+fn modpack_export_test() {
+    let dir = TempDir::new("modpack_export_test");
+    fs::create_dir_all(dir.join("config").join("jei")).unwrap();
+    fs::create_dir_all(dir.join("saves").join("world")).unwrap();
+    fs::create_dir_all(dir.join("mods")).unwrap();
+    fs::write(dir.join("config").join("jei").join("jei.toml"), "jei").unwrap();
+    fs::write(dir.join("saves").join("world").join("level.dat"), "level").unwrap();
+    fs::write(dir.join("mods").join("a.jar"), "a").unwrap();
+    fs::write(dir.join("options.txt"), "options").unwrap();
+
+    let folders = exportable_folders(&dir).unwrap();
+    assert_eq!(folders, vec!["config".to_string(), "mods".to_string()]);
+    assert_eq!(
+        default_folders(ExportFormat::ServerPackage, &folders),
+        folders
+    );
+
+    let files = walk(&dir, &dir.join("config")).unwrap();
+    let manifest = FileManifest::generate("1", &dir, &files).unwrap();
+    assert_eq!(manifest.files.len(), 1);
+    assert_eq!(manifest.files[0].path, "config/jei/jei.toml");
+    assert_eq!(manifest.files[0].size, 3);
+    // sha1 of "jei"
+    assert_eq!(
+        manifest.files[0].sha1,
+        "ec194f3b8728998675612deb427f51ec95fd01c3"
+    );
+
+    // Published manifest lets launcher install the package
+    let out = TempDir::new("modpack_export_test_out");
+    let options = ExportOptions {
+        format: ExportFormat::ServerPackage,
+        folders,
+        output: out.join("funnycraft.zip"),
+    };
+    export_server_package(&dir, &options).unwrap();
+    let data = fs::read_to_string(out.join("funnycraft.manifest.json")).unwrap();
+    let exported: FileManifest = serde_json::from_str(&data).unwrap();
+    assert_eq!(exported.files.len(), 2);
+    assert_eq!(
+        exported.package,
+        Some(PackageInfo {
+            sha256: sha256_file(&options.output).unwrap(),
+            size: fs::metadata(&options.output).unwrap().len(),
+        })
+    );
+}
+
+#[test]
+fn manifest_diff_test() {
+    let dir = TempDir::new("manifest_diff_test");
+    fs::create_dir_all(dir.join("config")).unwrap();
+    fs::create_dir_all(dir.join("screenshots")).unwrap();
+    fs::create_dir_all(dir.join("mods")).unwrap();
+    fs::write(dir.join("game.jar"), "jei").unwrap();
+    fs::write(dir.join("changed.jar"), "changed").unwrap();
+    fs::write(dir.join("config").join("jei.toml"), "edited by user").unwrap();
+    fs::write(dir.join("screenshots").join("1.png"), "png").unwrap();
+    fs::write(dir.join("options.txt"), "options").unwrap();
+    fs::write(dir.join("mods").join("user.jar"), "user").unwrap();
+    fs::write(dir.join("junk.txt"), "junk").unwrap();
+
+    let entry = |path: &str, policy: Policy| FileEntry {
+        path: path.to_string(),
+        // sha1 of "jei"
+        sha1: "ec194f3b8728998675612deb427f51ec95fd01c3".to_string(),
+        size: 3,
+        policy,
+        url: None,
+    };
+    let manifest = FileManifest {
+        version: "1".to_string(),
+        files: vec![
+            entry("game.jar", Policy::Always),
+            entry("changed.jar", Policy::Always),
+            entry("config/jei.toml", Policy::Once),
+            entry("config/ignored.toml", Policy::Ignore),
+            entry("missing.jar", Policy::Always),
+            entry("options.txt", Policy::Always),
+        ],
+        package: None,
+    };
+
+    let diff = manifest
+        .diff(
+            &dir,
+            &["mods/user.jar".to_string()],
+            &mut HashCache::default(),
+        )
+        .unwrap();
+    let paths =
+        |entries: &[FileEntry]| -> Vec<String> { entries.iter().map(|e| e.path.clone()).collect() };
+    assert_eq!(paths(&diff.missing), vec!["missing.jar".to_string()]);
+    assert_eq!(paths(&diff.modified), vec!["changed.jar".to_string()]);
+    assert_eq!(diff.extra, vec!["junk.txt".to_string()]);
+    assert!(!diff.is_valid());
+
+    let manifest: FileManifest = serde_json::from_str(
+        r#"{ "version": "1", "files": [{ "path": "a", "sha1": "b", "size": 1 }] }"#,
+    )
+    .unwrap();
+    assert_eq!(manifest.files[0].policy, Policy::Always);
+    assert_eq!(manifest.package, None);
+
+    let manifest: FileManifest = serde_json::from_str(
+        r#"{ "version": "1", "files": [], "package": { "sha256": "ab", "size": 10 } }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        manifest.package,
+        Some(PackageInfo {
+            sha256: "ab".to_string(),
+            size: 10
+        })
+    );
+}
+
+#[test]
+fn obsolete_files_test() {
+    let entry = |path: &str| FileEntry {
+        path: path.to_string(),
+        sha1: String::new(),
+        size: 0,
+        policy: Policy::Always,
+        url: None,
+    };
+    let manifest = FileManifest {
+        version: "2".to_string(),
+        files: vec![entry("config/a.toml"), entry("libraries/new.jar")],
+        package: None,
+    };
+    let managed = vec![
+        "config/a.toml".to_string(),
+        "libraries/old.jar".to_string(),
+        "options.txt".to_string(),
+    ];
+
+    assert_eq!(
+        obsolete(&managed, &manifest),
+        vec!["libraries/old.jar".to_string()]
+    );
+    assert_eq!(
+        entry("config/a.toml").urls()[0],
+        format!("{}{}/config/a.toml", MIRRORS[0], GET_MINECRAFT_FILES)
+    );
+}
+
+#[test]
+fn download_engine_test() {
+    let url = serve_files(Duration::from_millis(50));
+    let dir = TempDir::new("download_engine_test");
+
+    let sha1 = |size: usize| {
+        use sha1::{Digest, Sha1};
+        let body: Vec<u8> = served_body(size);
+        hex(&Sha1::digest(&body))
+    };
+    let tasks = |name: &str| -> Vec<Task> {
+        let mut tasks: Vec<Task> = (1..=16)
+            .map(|i| Task {
+                urls: vec![format!("{url}/{}", i * 100)],
+                path: dir.join(name).join(format!("{i}.bin")),
+                sha1: Some(sha1(i * 100)),
+                size: Some(i as u64 * 100),
+            })
+            .collect();
+        // Downloaded by ranges
+        tasks.push(Task {
+            urls: vec![format!("{url}/100000")],
+            path: dir.join(name).join("big.bin"),
+            sha1: Some(sha1(100000)),
+            size: Some(100000),
+        });
+        tasks
+    };
+    let run = |connections: usize, tasks: &[Task]| {
+        let engine = DownloadEngine::new(EngineOptions {
+            connections,
+            per_host: connections,
+            chunk_size: 16 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+        let start = Instant::now();
+        engine.run(tasks, |_| Ok(())).unwrap();
+        start.elapsed()
+    };
+
+    let sequential_tasks = tasks("sequential");
+    let sequential = run(1, &sequential_tasks);
+    let parallel_tasks = tasks("parallel");
+    let parallel = run(8, &parallel_tasks);
+
+    for task in &parallel_tasks {
+        assert_eq!(fs::metadata(&task.path).unwrap().len(), task.size.unwrap());
+    }
+    assert!(
+        parallel * 2 < sequential,
+        "parallel: {parallel:?}, sequential: {sequential:?}"
+    );
+
+    // Broken hash fails the whole run
+    let broken = vec![Task {
+        urls: vec![format!("{url}/100")],
+        path: dir.join("broken.bin"),
+        sha1: Some(sha1(99)),
+        size: None,
+    }];
+    assert!(DownloadEngine::new(EngineOptions::default())
+        .unwrap()
+        .run(&broken, |_| Ok(()))
+        .is_err());
+    assert!(!dir.join("broken.bin").exists());
+}
+
+#[test]
+fn package_download_test() {
+    let url = serve_files(Duration::ZERO);
+    let dir = TempDir::new("package_download_test");
+    let client = reqwest::blocking::Client::new();
+    let urls = vec![format!("{url}/20000")];
+    let path = dir.join("funnycraft.zip");
+    let sha256 = "290c84b9b148f3bc4dc2c6cbc847910f611e446e722eae6969438db9f4aecd57";
+
+    let mut options = DownloadOptions {
+        chunk_size: 16 * 1024,
+        checksum: Some(Checksum::Sha256(sha256.replace('2', "3"))),
+        retry: RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+        },
+        ..Default::default()
+    };
+
+    // Package which doesn't match manifest isn't downloaded
+    let package = PackageInfo {
+        sha256: sha256.to_string(),
+        size: 19999,
+    };
+    let e = package_size(&client, &urls, &package, &options).unwrap_err();
+    assert!(e.to_string().contains("19999"));
+    let package = PackageInfo {
+        size: 20000,
+        ..package
+    };
+    assert_eq!(
+        package_size(&client, &urls, &package, &options).unwrap(),
+        20000
+    );
+
+    // Corrupted package is downloaded again on every attempt
+    let mut completed = 0;
+    let e = download_package(&client, &urls, &path, &options, |p| {
+        if p.bytes == p.total_bytes {
+            completed += 1;
+        }
+        Ok(())
+    })
+    .unwrap_err();
+    assert!(e.is::<ChecksumMismatch>());
+    assert_eq!(completed, 3);
+    assert!(!path.exists());
+
+    options.checksum = Some(Checksum::Sha256(package.sha256.clone()));
+    download_package(&client, &urls, &path, &options, |_| Ok(())).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), 20000);
+}
+
+#[test]
+fn last_validation_test() {
+    let dir = TempDir::new("offline_test");
+    fs::create_dir_all(dir.join("mods")).unwrap();
+    fs::write(dir.join("game.jar"), "game").unwrap();
+    fs::write(dir.join("mods/a.jar"), "mod a").unwrap();
+    fs::write(dir.join("options.txt"), "fov:70").unwrap();
+
+    use sha1::{Digest, Sha1};
+    let entry = |path: &str, data: &str, policy| FileEntry {
+        path: path.to_string(),
+        sha1: hex(&Sha1::digest(data.as_bytes())),
+        size: data.len() as u64,
+        policy,
+        url: None,
+    };
+    let manifest = FileManifest {
+        version: "3".to_string(),
+        files: vec![
+            entry("game.jar", "game", Policy::Always),
+            entry("mods/a.jar", "mod a", Policy::Always),
+            entry("options.txt", "fov:70", Policy::Once),
+        ],
+        package: None,
+    };
+
+    let validation = LastValidation::record(&manifest, &dir).unwrap();
+    assert_eq!(validation.version, "3");
+    assert_eq!(validation.files.len(), 2);
+    let mut cache = HashCache::default();
+    assert!(validation.check(&dir, &mut cache).unwrap().is_empty());
+    assert_eq!(cache.len(), 2);
+
+    // User settings are not checked, same size content is caught by hash
+    fs::write(dir.join("options.txt"), "fov:110").unwrap();
+    fs::write(dir.join("mods/a.jar"), "mod b").unwrap();
+    fs::remove_file(dir.join("game.jar")).unwrap();
+    assert_eq!(
+        validation.check(&dir, &mut cache).unwrap(),
+        vec!["game.jar".to_string(), "mods/a.jar".to_string()]
+    );
+}
+
+#[test]
+fn repair_clear_caches_test() {
+    let dir = TempDir::new("repair_test");
+    fs::create_dir_all(dir.join("versions/1.16.5/natives")).unwrap();
+    fs::create_dir_all(dir.join("libraries/net/minecraft")).unwrap();
+    fs::write(dir.join("versions/1.16.5/natives/lwjgl.dll"), "dll").unwrap();
+    fs::write(dir.join("versions/1.16.5/1.16.5.jar"), "jar").unwrap();
+    fs::write(dir.join("libraries/net/minecraft/client-srg.jar"), "srg").unwrap();
+    fs::write(dir.join("extra.txt"), "user").unwrap();
+
+    let extra = vec![
+        "extra.txt".to_string(),
+        "libraries/net/minecraft/client-srg.jar".to_string(),
+    ];
+    let cleared = clear_caches(&dir, &extra).unwrap();
+    assert_eq!(
+        cleared,
+        vec![
+            "libraries/net/minecraft/client-srg.jar".to_string(),
+            "versions/1.16.5/natives".to_string(),
+        ]
+    );
+    assert!(!dir.join("versions/1.16.5/natives").exists());
+    assert!(dir.join("versions/1.16.5/1.16.5.jar").exists());
+    // Only library leftovers are removed
+    assert!(dir.join("extra.txt").exists());
+
+    let report = RepairReport {
+        version: "3".to_string(),
+        checked: 10,
+        missing: vec!["versions/1.16.5/natives/lwjgl.dll".to_string()],
+        modified: Vec::new(),
+        cleared,
+    };
+    let summary = report.summary();
+    assert!(summary.starts_with("Game 3 repaired. Checked 10 files."));
+    assert!(summary.contains("Restored 1 missing:\nversions/1.16.5/natives/lwjgl.dll"));
+    assert!(!summary.contains("All files are valid."));
+}
+
+#[test]
+fn download_engine_cancel_test() {
+    let url = serve_files(Duration::from_millis(20));
+    let dir = TempDir::new("download_engine_cancel_test");
+
+    // Unfinished temp files are removed
+    let cancel = CancelToken::default();
+    let engine = DownloadEngine::new(EngineOptions {
+        connections: 2,
+        chunk_size: 16 * 1024,
+        cancel: cancel.clone(),
+        ..Default::default()
+    })
+    .unwrap();
+    let tasks: Vec<Task> = (1..=4)
+        .map(|i| Task {
+            urls: vec![format!("{url}/100000")],
+            path: dir.join("engine").join(format!("{i}.bin")),
+            sha1: None,
+            size: Some(100000),
+        })
+        .collect();
+    let e = engine
+        .run(&tasks, |p| {
+            if p.bytes > 0 {
+                cancel.cancel();
+            }
+            Ok(())
+        })
+        .unwrap_err();
+    assert!(is_cancelled(e.as_ref()));
+    let left: Vec<_> = fs::read_dir(dir.join("engine"))
+        .unwrap()
+        .flatten()
+        .filter(|e| e.path().extension().is_some_and(|e| e == "tmp"))
+        .collect();
+    assert!(left.is_empty());
+}
+
+#[test]
+fn download_engine_failover_test() {
+    let dead = dead_url();
+    let alive = serve_files(Duration::ZERO);
+    let dir = TempDir::new("download_engine_failover_test");
+
+    let tasks: Vec<Task> = (1..=10)
+        .map(|i| Task {
+            urls: vec![
+                format!("{dead}/{}", i * 100),
+                format!("{alive}/{}", i * 100),
+            ],
+            path: dir.join(format!("{i}.bin")),
+            sha1: None,
+            size: Some(i as u64 * 100),
+        })
+        .collect();
+    let engine = DownloadEngine::new(EngineOptions {
+        connections: 1,
+        retry: RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_millis(400),
+        },
+        ..Default::default()
+    })
+    .unwrap();
+    let start = Instant::now();
+    engine.run(&tasks, |_| Ok(())).unwrap();
+
+    // Only the first file waits for retries of the dead mirror
+    assert!(
+        start.elapsed() < Duration::from_millis(1500),
+        "{:?}",
+        start.elapsed()
+    );
+    for task in &tasks {
+        assert_eq!(fs::metadata(&task.path).unwrap().len(), task.size.unwrap());
+    }
+}
+
+#[test]
+fn keep_paths_test() {
+    let game = TempDir::new("keep_paths_test");
+    fs::create_dir_all(game.join("mods")).unwrap();
+    fs::write(game.join("mods/old.jar"), "old").unwrap();
+    fs::write(game.join("mods/user.jar"), "user").unwrap();
+    fs::write(game.join("mods/off.jar.disabled"), "off").unwrap();
+
+    let instance = Instance {
+        managed_mods: vec!["old.jar".to_string()],
+        user_mods: vec!["user.jar".to_string()],
+        ..Default::default()
+    };
+    let keep = keep_paths(&game, &instance).unwrap();
+    assert!(keep.contains(&"saves".to_string()));
+    assert!(keep.contains(&"mods/user.jar".to_string()));
+    assert!(keep.contains(&"mods/off.jar.disabled".to_string()));
+    assert!(!keep.contains(&"mods/old.jar".to_string()));
+}
@@ -0,0 +1,118 @@
+use std::{collections::HashSet, error::Error, fs, sync::mpsc::Sender};
+
+use log::{error, info};
+
+use crate::{
+    launcher::commands::{Command, DownloadProgress, Phase},
+    utils::{archive::safe_join, download::CancelToken},
+};
+
+use super::{
+    downloader::download_minecraft,
+    engine::{DownloadEngine, EngineOptions, Task},
+    instance::Instance,
+    manifest::{is_user_path, FileManifest, ManifestDiff, Policy},
+};
+
+pub const UPDATER: &str = "MINECRAFT/UPDATER";
+
+// Files installed earlier which are not in manifest anymore
+pub fn obsolete(managed: &[String], manifest: &FileManifest) -> Vec<String> {
+    let listed: HashSet<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+
+    managed
+        .iter()
+        .filter(|p| !listed.contains(p.as_str()) && !is_user_path(p))
+        .cloned()
+        .collect()
+}
+
+fn download_files(
+    data_sender: &Sender<Command>,
+    diff: &ManifestDiff,
+    cancel: &CancelToken,
+) -> Result<(), Box<dyn Error>> {
+    let game_dir = Instance::game_dir()?;
+
+    let mut tasks = Vec::new();
+    for entry in diff.missing.iter().chain(diff.modified.iter()) {
+        info!(target: UPDATER, "Queued \"{}\".", entry.path);
+        tasks.push(Task {
+            urls: entry.urls(),
+            path: safe_join(&game_dir, &entry.path)?,
+            sha1: Some(entry.sha1.clone()),
+            size: Some(entry.size),
+        });
+    }
+
+    let engine = DownloadEngine::new(EngineOptions {
+        cancel: cancel.clone(),
+        ..Default::default()
+    })?;
+    engine.run(&tasks, |progress| {
+        match data_sender.send(Command::DOWNLOAD(DownloadProgress {
+            phase: Phase::Game,
+            ..progress
+        })) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!(target: UPDATER, "Error while sending \"DOWNLOAD\" command to control thread.");
+                Err(e.into())
+            }
+        }
+    })
+}
+
+// Bring game dir to manifest state. Fresh install is downloaded as single archive, otherwise
+// only missing and modified files are downloaded.
+pub fn update_game(
+    data_sender: Sender<Command>,
+    manifest: &FileManifest,
+    diff: &ManifestDiff,
+    cancel: &CancelToken,
+) -> Result<(), Box<dyn Error>> {
+    let game_dir = Instance::game_dir()?;
+    let mut instance = Instance::load()?;
+
+    let fresh = !game_dir.exists()
+        || (instance.managed_files.is_empty() && diff.missing.len() == manifest.files.len());
+
+    if fresh {
+        info!(target: UPDATER, "Game not installed, downloading full archive...");
+        let package = manifest
+            .package
+            .as_ref()
+            .ok_or("Update server didn't publish game package checksum.")?;
+        let unpacked_size = manifest.files.iter().map(|f| f.size).sum();
+        download_minecraft(data_sender, cancel, package, unpacked_size)?;
+    } else {
+        info!(
+            target: UPDATER,
+            "Updating game to {}: {} missing, {} modified files.",
+            manifest.version,
+            diff.missing.len(),
+            diff.modified.len()
+        );
+        download_files(&data_sender, diff, cancel)?;
+
+        for path in obsolete(&instance.managed_files, manifest) {
+            let file = safe_join(&game_dir, &path)?;
+            if file.is_file() {
+                info!(target: UPDATER, "Removing obsolete \"{path}\"...");
+                fs::remove_file(file)?;
+            }
+        }
+    }
+
+    instance.managed_files = manifest
+        .files
+        .iter()
+        .filter(|f| f.policy != Policy::Ignore)
+        .map(|f| f.path.clone())
+        .collect();
+    instance.save()?;
+
+    info!(target: UPDATER, "Game updated.");
+
+    Ok(())
+}
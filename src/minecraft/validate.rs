@@ -1,27 +1,60 @@
 use std::error::Error;
 
-use log::{debug, info};
+use log::{debug, info, warn};
 
-use crate::utils::constants::{GET_MINECRAFT_CHECKSUM, URL};
+use crate::minecraft::{
+    instance::Instance,
+    manifest::{FileManifest, ManifestDiff},
+    mod_manager::user_owned_files,
+};
+use crate::utils::hash_cache::HashCache;
 
 pub const VALIDATOR: &str = "MINECRAFT/VALIDATOR";
 
-pub fn is_valid_files() -> Result<bool, Box<dyn Error>> {
-    let mut path = dirs::data_dir().ok_or("Couldn't get data dir")?;
-    path.push(".funnycraft");
-
-    info!(target: VALIDATOR, "Checking game hash...");
-
-    let checksum =
-        checksumdir::checksumdir(path.to_str().ok_or("Couldn't convert PathBuf to str.")?)?;
-    let resp = reqwest::blocking::get(format!("{}{}", URL, GET_MINECRAFT_CHECKSUM))?
-        .text()
-        .unwrap_or_default();
-    debug!(target: VALIDATOR, "Local hash: {checksum} | Server hash: {resp}");
-
-    if resp != checksum {
-        return Ok(false);
+// Unchanged files are not hashed again unless `full` re-verify is requested
+pub fn validate_files(full: bool) -> Result<(FileManifest, ManifestDiff), Box<dyn Error>> {
+    let path = Instance::game_dir()?;
+    let instance = Instance::load()?;
+
+    info!(target: VALIDATOR, "Checking game files...");
+
+    // Mods are synchronized separately, user's mods are not part of the game at all
+    let mut excluded: Vec<String> = user_owned_files(&path.join("mods"), &instance)?
+        .into_iter()
+        .chain(instance.managed_mods.iter().cloned())
+        .map(|f| format!("mods/{f}"))
+        .collect();
+    excluded.sort();
+    excluded.dedup();
+    debug!(target: VALIDATOR, "Excluded from validation: {:?}", excluded);
+
+    let manifest = FileManifest::load()?;
+    let mut cache = match full {
+        true => HashCache::default(),
+        false => HashCache::load(),
+    };
+    let diff = manifest.diff(&path, &excluded, &mut cache)?;
+    cache.save().unwrap_or_else(|e| {
+        warn!(target: VALIDATOR, "Couldn't save hash cache. Error: {e}");
+    });
+
+    for entry in &diff.missing {
+        warn!(target: VALIDATOR, "Missing: {}", entry.path);
     }
-
-    Ok(true)
+    for entry in &diff.modified {
+        warn!(target: VALIDATOR, "Modified: {}", entry.path);
+    }
+    for file in &diff.extra {
+        debug!(target: VALIDATOR, "Extra: {file}");
+    }
+    info!(
+        target: VALIDATOR,
+        "Manifest {} | Missing: {} | Modified: {} | Extra: {}",
+        manifest.version,
+        diff.missing.len(),
+        diff.modified.len(),
+        diff.extra.len()
+    );
+
+    Ok((manifest, diff))
 }
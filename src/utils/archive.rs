@@ -0,0 +1,292 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::Read,
+    path::{Component, Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use zip::ZipArchive;
+
+use crate::launcher::commands::DownloadProgress;
+
+pub const ARCHIVE: &str = "ARCHIVE";
+
+// GUI handles one command per frame, so progress is not sent more often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+// Join archive entry path to base dir. Rejects absolute paths and "..", so entry couldn't be
+// written outside of base dir.
+pub fn safe_join(base: &Path, relative: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let relative = Path::new(relative);
+    let mut ret = base.to_path_buf();
+
+    for component in relative.components() {
+        match component {
+            Component::Normal(c) => ret.push(c),
+            Component::CurDir => (),
+            _ => return Err(format!("Unsafe path in archive: {:?}", relative).into()),
+        }
+    }
+
+    if ret == base {
+        return Err(format!("Empty path in archive: {:?}", relative).into());
+    }
+
+    Ok(ret)
+}
+
+// Whether symlink in `dir` pointing to `target` resolves inside of base dir. Both dirs should be
+// canonical. ".." is allowed only at start of target, so other symlinks in its path couldn't
+// lead out.
+pub fn link_inside(base: &Path, dir: &Path, target: &str) -> bool {
+    let mut resolved = match dir.strip_prefix(base) {
+        Ok(p) => p.to_path_buf(),
+        Err(_) => return false,
+    };
+    let mut normal = false;
+
+    for component in Path::new(target).components() {
+        match component {
+            Component::Normal(c) => {
+                normal = true;
+                resolved.push(c);
+            }
+            Component::CurDir => (),
+            Component::ParentDir if !normal => {
+                if !resolved.pop() {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &Path, _: u32) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    std::os::unix::fs::symlink(target, path)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn symlink(target: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    log::warn!(target: ARCHIVE, "Symlinks are not supported, skipping {:?} -> \"{target}\".", path);
+    Ok(())
+}
+
+// Uncompressed size of all entries, from zip central directory
+pub fn extracted_size(archive: &Path) -> Result<u64, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(archive)?)?;
+
+    let mut size = 0;
+    for i in 0..archive.len() {
+        size += archive.by_index(i)?.size();
+    }
+
+    Ok(size)
+}
+
+// Extract zip into dir. Entries escaping dir by path or symlink are rejected. Progress counts
+// entries and uncompressed bytes.
+pub fn extract(
+    archive: &Path,
+    dir: &Path,
+    mut progress: impl FnMut(DownloadProgress) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(archive)?)?;
+    fs::create_dir_all(dir)?;
+
+    let mut state = DownloadProgress {
+        total_files: archive.len() as u64,
+        ..Default::default()
+    };
+    for i in 0..archive.len() {
+        state.total_bytes += archive.by_index(i)?.size();
+    }
+
+    let mut last = Instant::now();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let path = safe_join(dir, entry.name())?;
+
+        if last.elapsed() >= PROGRESS_INTERVAL {
+            last = Instant::now();
+            state.file = entry.name().to_string();
+            progress(state.clone())?;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mode = entry.unix_mode();
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+        } else if mode.is_some_and(|m| m & S_IFMT == S_IFLNK) {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            let parent = path.parent().ok_or("Wrong symlink path.")?.canonicalize()?;
+            if !link_inside(&dir.canonicalize()?, &parent, &target) {
+                return Err(format!(
+                    "Unsafe symlink in archive: \"{}\" -> \"{target}\"",
+                    entry.name()
+                )
+                .into());
+            }
+            symlink(&target, &path)?;
+            state.files += 1;
+            state.bytes += entry.size();
+            continue;
+        } else {
+            std::io::copy(&mut entry, &mut File::create(&path)?)?;
+        }
+
+        if let Some(mode) = mode {
+            set_mode(&path, mode)?;
+        }
+        state.files += 1;
+        state.bytes += entry.size();
+    }
+
+    state.file = String::new();
+    progress(state)?;
+
+    Ok(())
+}
+
+// Extract zip into staging dir next to target and swap it with target. `keep` paths of old
+// target are moved into new one. When archive has single top dir named as target, its content
+// is used.
+pub fn install(
+    archive: &Path,
+    target: &Path,
+    keep: &[&str],
+    progress: impl FnMut(DownloadProgress) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Wrong target dir name.")?;
+    let staging = target.with_file_name(format!("{name}.staging"));
+
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+
+    info!(target: ARCHIVE, "Extracting {:?} to {:?}...", archive, staging);
+    if let Err(e) = extract(archive, &staging, progress) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    let entries = fs::read_dir(&staging)?.collect::<Result<Vec<_>, _>>()?;
+    let root = match entries.as_slice() {
+        [e] if e.file_name() == name && e.path().is_dir() => e.path(),
+        _ => staging.clone(),
+    };
+
+    swap(&root, target, keep)?;
+
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+
+    Ok(())
+}
+
+// Replace target dir with `new` dir by renames, so target is never half written. `keep` paths of
+// old target are moved into new one. Both dirs have to be on the same file system.
+pub fn swap(new: &Path, target: &Path, keep: &[&str]) -> Result<(), Box<dyn Error>> {
+    let name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Wrong target dir name.")?;
+    let old = target.with_file_name(format!("{name}.old"));
+
+    recover(target, keep)?;
+
+    if target.exists() {
+        fs::rename(target, &old)?;
+    }
+
+    if let Err(e) = fs::rename(new, target) {
+        // Put old dir back, so game stays playable
+        if old.exists() {
+            fs::rename(&old, target)?;
+        }
+        return Err(e.into());
+    }
+
+    move_paths(&old, target, keep)?;
+
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    info!(target: ARCHIVE, "Installed {:?}.", target);
+
+    Ok(())
+}
+
+// Finish swap which was interrupted, so old dir with player's files is never just removed.
+// Target is restored from old dir if it's missing, otherwise `keep` paths are moved into it.
+pub fn recover(target: &Path, keep: &[&str]) -> Result<(), Box<dyn Error>> {
+    let name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Wrong target dir name.")?;
+    let old = target.with_file_name(format!("{name}.old"));
+
+    if !old.exists() {
+        return Ok(());
+    }
+
+    warn!(target: ARCHIVE, "Found {:?} of interrupted install, recovering it.", old);
+    if target.exists() {
+        move_paths(&old, target, keep)?;
+        fs::remove_dir_all(&old)?;
+    } else {
+        fs::rename(&old, target)?;
+    }
+
+    Ok(())
+}
+
+// Move `paths` which exist in `from` dir into `to` dir, replacing ones there
+fn move_paths(from: &Path, to: &Path, paths: &[&str]) -> Result<(), Box<dyn Error>> {
+    for path in paths {
+        let (from, to) = (from.join(path), to.join(path));
+        if from.exists() {
+            debug!(target: ARCHIVE, "Keeping {:?}.", to);
+            if to.is_dir() {
+                fs::remove_dir_all(&to)?;
+            } else if to.exists() {
+                fs::remove_file(&to)?;
+            }
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(from, to)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::{error::Error, fs, io, path::Path, path::PathBuf};
+
+use log::{debug, info, warn};
+
+use super::{constants::LAUNCHER_DIR, download::ResumeState};
+
+pub const CACHE: &str = "CACHE";
+
+// Launcher managed dir for downloads which are removed after install
+pub fn cache_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let path = dirs::data_dir()
+        .ok_or("OS data dir not found.")?
+        .join(LAUNCHER_DIR)
+        .join("cache");
+
+    if !path.exists() {
+        fs::create_dir_all(&path)?;
+    }
+
+    Ok(path)
+}
+
+pub fn cache_file(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(cache_dir()?.join(name))
+}
+
+// Size of all files in dir, recursively
+pub fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size += match meta.is_dir() {
+            true => dir_size(&entry.path())?,
+            false => meta.len(),
+        };
+    }
+
+    Ok(size)
+}
+
+pub fn cache_size() -> Result<u64, Box<dyn Error>> {
+    Ok(dir_size(&cache_dir()?)?)
+}
+
+// Remove cached download with its resume state once it's installed
+pub fn remove_cached(path: &Path) {
+    ResumeState::remove(path);
+    match fs::remove_file(path) {
+        Ok(_) => debug!(target: CACHE, "Removed {:?}.", path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => warn!(target: CACHE, "Couldn't remove {:?}. Error: {e}", path),
+    }
+}
+
+// Remove everything from dir. Returns reclaimed bytes.
+pub fn clear_dir(dir: &Path) -> io::Result<u64> {
+    let size = dir_size(dir)?;
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        match path.is_dir() {
+            true => fs::remove_dir_all(path)?,
+            false => fs::remove_file(path)?,
+        }
+    }
+
+    Ok(size)
+}
+
+pub fn clear_cache() -> Result<u64, Box<dyn Error>> {
+    let size = clear_dir(&cache_dir()?)?;
+    info!(target: CACHE, "Cache cleared, {size} bytes reclaimed.");
+
+    Ok(size)
+}
@@ -5,12 +5,27 @@ pub const MINECRAFT_FORGE: &str = "1.16.5-forge-36.2.34";
 pub const WORKING_DIR: &str = ".funnycraft";
 pub const LAUNCHER_DIR: &str = ".funnylauncher";
 pub const URL: &str = "http://localhost:8080";
+// Download servers in order of preference
+pub const MIRRORS: [&str; 1] = [URL];
 pub const LOGIN_URL: &str = "/api/v1/auth/sign-in";
 pub const REGISTRATION_URL: &str = "https://localhost:8080/signup";
 pub const GET_USER_URL: &str = "/api/v1/account/me";
 pub const UPDATE_USER_ONLINE_URL: &str = "/api/v1/updateOnline";
+pub const GET_SESSION_TOKEN_URL: &str = "/api/v1/session/token";
 pub const GET_NEWS_LIST: &str = "/api/v1/news/list";
 pub const GET_LAUNCHER_VERSION: &str = "/api/v1/getLatestVersion";
 pub const GET_LAUNCHER_UPDATE: &str = "/api/v1/launcher/downloadLatestLauncher&os=linux";
-pub const GET_MINECRAFT_CHECKSUM: &str = "/api/v1/minecraft/checksum";
+// Base64 detached Ed25519 signature of launcher binary
+pub const GET_LAUNCHER_SIGNATURE: &str =
+    "/api/v1/launcher/downloadLatestLauncherSignature&os=linux";
+// Hex Ed25519 public key of release signing key, given at build time. Builds without it
+// never install updates. See RELEASING.md.
+pub const LAUNCHER_PUBLIC_KEY: Option<&str> = option_env!("FUNNYLAUNCHER_UPDATE_KEY");
+pub const GET_MINECRAFT_MANIFEST: &str = "/api/v1/minecraft/manifest";
+pub const GET_MINECRAFT_FILES: &str = "/api/v1/minecraft/files";
 pub const GET_MINECRAFT: &str = "/api/v1/minecraft";
+pub const GET_MODS_MANIFEST: &str = "/api/v1/minecraft/mods";
+// CurseForge file metadata, proxied by server so API key is not shipped with launcher
+pub const GET_CURSEFORGE_FILE: &str = "/api/v1/curseforge/mods";
+pub const YGGDRASIL_URL: &str = "/api/yggdrasil";
+pub const AUTHLIB_INJECTOR_META: &str = "https://authlib-injector.yushi.moe/artifact/latest.json";
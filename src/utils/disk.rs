@@ -0,0 +1,86 @@
+use std::{error::Error, fmt, io, path::Path};
+
+use log::{error, info};
+
+pub const DISK: &str = "DISK";
+
+#[derive(Debug)]
+pub struct NotEnoughSpace {
+    pub required: u64,
+    pub available: u64,
+}
+
+impl fmt::Display for NotEnoughSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Not enough disk space: {} bytes required, {} bytes available.",
+            self.required, self.available
+        )
+    }
+}
+
+impl Error for NotEnoughSpace {}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn available(path: &Path) -> io::Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Blocks available to unprivileged user
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn available(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut free = 0;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut free,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(free)
+}
+
+// Free space on filesystem of path. Path may not exist yet, its nearest existing parent is used.
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No existing parent dir."))?;
+
+    available(existing)
+}
+
+pub fn ensure_space(path: &Path, required: u64) -> Result<(), Box<dyn Error>> {
+    let available = free_space(path)?;
+    info!(target: DISK, "Space required: {required} | available: {available} on {:?}.", path);
+
+    if available < required {
+        error!(target: DISK, "Not enough disk space on {:?}.", path);
+        return Err(NotEnoughSpace {
+            required,
+            available,
+        }
+        .into());
+    }
+
+    Ok(())
+}
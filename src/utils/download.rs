@@ -0,0 +1,627 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, error, info, warn};
+use reqwest::{
+    blocking::{Client, Response},
+    header::{HeaderValue, ETAG, IF_RANGE, RANGE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::launcher::commands::DownloadProgress;
+
+use super::{
+    constants::MIRRORS,
+    hash::{sha1_file, sha256_file},
+    http::client_builder,
+};
+
+pub const DOWNLOADER: &str = "DOWNLOAD";
+
+pub const CHUNK_SIZE: u32 = 1024 * 1024;
+
+struct PartialRangeIter {
+    start: u64,
+    end: u64,
+    buffer_size: u32,
+}
+
+impl PartialRangeIter {
+    pub fn new(start: u64, end: u64, buffer_size: u32) -> Result<Self, Box<dyn Error>> {
+        if buffer_size == 0 {
+            Err("invalid buffer_size, give a value greater than zero.")?;
+        }
+        Ok(PartialRangeIter {
+            start,
+            end,
+            buffer_size,
+        })
+    }
+}
+
+impl Iterator for PartialRangeIter {
+    type Item = (HeaderValue, u64);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start > self.end {
+            None
+        } else {
+            let prev_start = self.start;
+            self.start += std::cmp::min(self.buffer_size as u64, self.end - self.start + 1);
+            Some((
+                HeaderValue::from_str(&format!("bytes={}-{}", prev_start, self.start - 1))
+                    .expect("string provided by format!"),
+                prev_start,
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Checksum {
+    Sha1(String),
+    Sha256(String),
+}
+
+// Check downloaded file against expected size and hash
+pub fn verify(
+    path: &Path,
+    size: Option<u64>,
+    checksum: Option<&Checksum>,
+) -> Result<bool, Box<dyn Error>> {
+    if let Some(size) = size {
+        if fs::metadata(path)?.len() != size {
+            return Ok(false);
+        }
+    }
+
+    Ok(match checksum {
+        Some(Checksum::Sha1(h)) => sha1_file(path)?.eq_ignore_ascii_case(h),
+        Some(Checksum::Sha256(h)) => sha256_file(path)?.eq_ignore_ascii_case(h),
+        None => true,
+    })
+}
+
+// Transfer speed as exponential moving average, so one slow chunk doesn't make it jump
+#[derive(Debug)]
+pub struct SpeedMeter {
+    last: Instant,
+    last_bytes: u64,
+    speed: f64,
+}
+
+impl SpeedMeter {
+    // Weight of the newest sample
+    const SMOOTHING: f64 = 0.3;
+    // Shorter intervals give too noisy samples
+    const MIN_INTERVAL: Duration = Duration::from_millis(200);
+
+    pub fn new(bytes: u64) -> Self {
+        Self {
+            last: Instant::now(),
+            last_bytes: bytes,
+            speed: 0.,
+        }
+    }
+
+    pub fn update(&mut self, bytes: u64) -> f64 {
+        self.update_at(bytes, Instant::now())
+    }
+
+    pub fn update_at(&mut self, bytes: u64, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.last);
+        if elapsed < Self::MIN_INTERVAL {
+            return self.speed;
+        }
+
+        let current = bytes.saturating_sub(self.last_bytes) as f64 / elapsed.as_secs_f64();
+        self.speed = if self.speed == 0. {
+            current
+        } else {
+            Self::SMOOTHING * current + (1. - Self::SMOOTHING) * self.speed
+        };
+        self.last = now;
+        self.last_bytes = bytes;
+
+        self.speed
+    }
+}
+
+// Shared flags to stop or pause download from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    // Stopped without discarding, so download is resumed on next run
+    aborted: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Stop like `cancel`, but keep partial file and its resume state
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::Relaxed);
+        self.cancel();
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Block while download is paused. Cancelling paused download stops waiting.
+    pub fn wait_if_paused(&self) -> Result<(), Cancelled> {
+        while self.is_paused() && !self.is_cancelled() {
+            std::thread::sleep(Self::PAUSE_POLL);
+        }
+
+        match self.is_cancelled() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Download cancelled.")
+    }
+}
+
+impl Error for Cancelled {}
+
+// Downloaded file doesn't match expected size or hash. It's removed, so it could be retried.
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub file: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Checksum mismatch for \"{}\".", self.file)
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+pub fn is_cancelled(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<Cancelled>().is_some()
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // Attempts on one mirror, including the first one
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Exponential backoff before given retry (starting from 1). Half of delay is random, so
+    // clients don't hammer recovering server at the same moment.
+    pub fn delay(&self, retry: u32, random: f64) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let capped = exp.min(self.max_delay);
+
+        capped / 2 + capped.mul_f64(random.clamp(0., 1.) / 2.)
+    }
+
+    pub fn sleep(&self, retry: u32) {
+        let random = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as f64 / 1e9)
+            .unwrap_or(0.5);
+        std::thread::sleep(self.delay(retry, random));
+    }
+}
+
+// Statuses worth retrying, others won't change on repeat
+pub fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn is_transient_error(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || e.is_request() || e.is_body()
+}
+
+// Full urls of file on every mirror, in order of preference
+pub fn mirror_urls(path: &str) -> Vec<String> {
+    MIRRORS.iter().map(|m| format!("{m}{path}")).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub chunk_size: u32,
+    pub retry: RetryPolicy,
+    pub checksum: Option<Checksum>,
+    pub cancel: CancelToken,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: CHUNK_SIZE,
+            retry: RetryPolicy::default(),
+            checksum: None,
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+// Client for big downloads. Timeout is per chunk request, not for whole file.
+pub fn client() -> Result<Client, Box<dyn Error>> {
+    Ok(client_builder()?
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(60))
+        .build()?)
+}
+
+// Progress of unfinished download, stored next to downloaded file
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub url: String,
+    pub size: u64,
+    pub etag: Option<String>,
+    // Bytes confirmed written to file
+    pub downloaded: u64,
+}
+
+impl ResumeState {
+    fn path(file: &Path) -> PathBuf {
+        let mut name = file.file_name().unwrap_or_default().to_os_string();
+        name.push(".resume.json");
+        file.with_file_name(name)
+    }
+
+    pub fn load(file: &Path) -> Option<Self> {
+        let data = fs::read_to_string(Self::path(file)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub fn save(&self, file: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(Self::path(file), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn remove(file: &Path) {
+        let path = Self::path(file);
+        if path.exists() {
+            fs::remove_file(path).unwrap_or_else(|e| {
+                warn!(target: DOWNLOADER, "Couldn't remove resume state. Error: {e}");
+            });
+        }
+    }
+
+    // Offset to continue from. Download could be resumed only if it's the same content, which
+    // is confirmed by ETag, and file on disk has all confirmed bytes.
+    pub fn offset(&self, url: &str, size: u64, etag: Option<&str>, file_len: u64) -> u64 {
+        let same =
+            self.url == url && self.size == size && etag.is_some() && self.etag.as_deref() == etag;
+
+        if same && file_len >= self.downloaded && self.downloaded < size {
+            self.downloaded
+        } else {
+            0
+        }
+    }
+}
+
+// Send request to current mirror, retrying transient errors. When mirror stays unhealthy, next
+// one is used for this and following requests.
+pub fn send(
+    urls: &[String],
+    mirror: &mut usize,
+    retry: &RetryPolicy,
+    cancel: &CancelToken,
+    request: impl Fn(&str) -> reqwest::Result<Response>,
+) -> Result<Response, Box<dyn Error>> {
+    let mut last_error = String::from("No download urls.");
+
+    while *mirror < urls.len() {
+        let url = &urls[*mirror];
+
+        for attempt in 1..=retry.attempts.max(1) {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
+            let transient = match request(url) {
+                Ok(r) if r.status().is_success() => return Ok(r),
+                Ok(r) => {
+                    last_error = format!("Server error: {}", r.status());
+                    is_transient(r.status())
+                }
+                Err(e) => {
+                    last_error = e.to_string();
+                    is_transient_error(&e)
+                }
+            };
+
+            if !transient || attempt == retry.attempts {
+                break;
+            }
+            warn!(target: DOWNLOADER, "Request to \"{url}\" failed, retry {attempt}/{}. Error: {last_error}", retry.attempts - 1);
+            retry.sleep(attempt);
+        }
+
+        error!(target: DOWNLOADER, "Mirror \"{url}\" failed. Error: {last_error}");
+        *mirror += 1;
+    }
+
+    Err(last_error.into())
+}
+
+// Size of file on server, asked from the first healthy mirror
+pub fn content_length(
+    client: &Client,
+    urls: &[String],
+    options: &DownloadOptions,
+) -> Result<u64, Box<dyn Error>> {
+    let resp = send(urls, &mut 0, &options.retry, &options.cancel, |u| {
+        client.get(u).send()
+    })?;
+
+    Ok(resp
+        .content_length()
+        .ok_or("Couldn't get content length.")?)
+}
+
+// Download file by chunks with Range requests. Unfinished download is continued from the last
+// confirmed chunk, `If-Range` makes server send full new content if it was changed. Returning
+// error from `progress` cancels download. Cancelled download is removed with its resume state,
+// aborted one is kept for the next run.
+pub fn download(
+    client: &Client,
+    urls: &[String],
+    path: &Path,
+    options: &DownloadOptions,
+    progress: impl FnMut(DownloadProgress) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let result = download_chunks(client, urls, path, options, progress);
+
+    if let Err(e) = &result {
+        if is_cancelled(e.as_ref()) && !options.cancel.is_aborted() {
+            info!(target: DOWNLOADER, "Download cancelled, removing {:?}.", path);
+            ResumeState::remove(path);
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    result
+}
+
+fn download_chunks(
+    client: &Client,
+    urls: &[String],
+    path: &Path,
+    options: &DownloadOptions,
+    mut progress: impl FnMut(DownloadProgress) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    // Download is identified by main url, whichever mirror serves it
+    let url = urls.first().ok_or("No download urls.")?.as_str();
+    let mut mirror = 0;
+    let resp = send(urls, &mut mirror, &options.retry, &options.cancel, |u| {
+        client.get(u).send()
+    })?;
+
+    let size = match resp.content_length() {
+        Some(s) => Some(s),
+        None => {
+            error!(target: DOWNLOADER, "Couldn't get content length.");
+            None
+        }
+    }
+    .ok_or("Couldn't get content length.")?;
+    let etag = resp
+        .headers()
+        .get(ETAG)
+        .and_then(|e| e.to_str().ok())
+        .map(|e| e.to_string());
+    drop(resp);
+    debug!(target: DOWNLOADER, "Content length: {size} | ETag: {:?}", etag);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file_len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let start = ResumeState::load(path)
+        .map(|s| s.offset(url, size, etag.as_deref(), file_len))
+        .unwrap_or(0);
+
+    let mut file = if start > 0 {
+        info!(target: DOWNLOADER, "Resuming download from {start} of {size} bytes.");
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(start)?;
+        file.seek(SeekFrom::Start(start))?;
+        file
+    } else {
+        File::create(path)?
+    };
+
+    let mut state = ResumeState {
+        url: url.to_string(),
+        size,
+        etag: etag.clone(),
+        downloaded: start,
+    };
+
+    let ranges = match size {
+        0 => None,
+        _ => Some(PartialRangeIter::new(start, size - 1, options.chunk_size)?),
+    };
+
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let mut meter = SpeedMeter::new(start);
+    let mut report = |bytes: u64| {
+        progress(DownloadProgress {
+            file: file_name.clone(),
+            bytes,
+            total_bytes: size,
+            speed: meter.update(bytes),
+            ..Default::default()
+        })
+    };
+
+    'chunks: for (range, downloaded) in ranges.into_iter().flatten() {
+        options.cancel.wait_if_paused()?;
+        report(downloaded)?;
+
+        let mut retry = 0;
+        let n = loop {
+            let mut response = send(urls, &mut mirror, &options.retry, &options.cancel, |u| {
+                let mut request = client.get(u).header(RANGE, range.clone());
+                if let Some(etag) = &etag {
+                    request = request.header(IF_RANGE, etag.as_str());
+                }
+                request.send()
+            })?;
+
+            // Content was changed since download started, server sent it whole
+            if response.status() == StatusCode::OK {
+                warn!(target: DOWNLOADER, "Content changed on server, restarting download.");
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                std::io::copy(&mut response, &mut file)?;
+                break 'chunks;
+            }
+
+            match std::io::copy(&mut response, &mut file) {
+                Ok(n) => break n,
+                Err(e) if retry + 1 < options.retry.attempts => {
+                    retry += 1;
+                    warn!(target: DOWNLOADER, "Error while reading chunk, retry {retry}. Error: {e}");
+                    file.set_len(downloaded)?;
+                    file.seek(SeekFrom::Start(downloaded))?;
+                    options.retry.sleep(retry);
+                }
+                Err(e) => {
+                    error!(target: DOWNLOADER, "Error while writing downloaded data to file.");
+                    return Err(e.into());
+                }
+            }
+        };
+
+        state.downloaded = downloaded + n;
+        file.sync_data()?;
+        state.save(path)?;
+    }
+    drop(file);
+    report(size)?;
+
+    ResumeState::remove(path);
+
+    // Short read of a chunk leaves file truncated
+    if !verify(path, Some(size), options.checksum.as_ref())? {
+        error!(target: DOWNLOADER, "Checksum mismatch for \"{url}\".");
+        fs::remove_file(path)?;
+        return Err(ChecksumMismatch { file: file_name }.into());
+    }
+
+    Ok(())
+}
+
+// Download small file at once through temp file
+pub fn download_small(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    size: Option<u64>,
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn Error>> {
+    let tmp = path.with_file_name(format!(
+        "{}.tmp",
+        path.file_name()
+            .and_then(|f| f.to_str())
+            .ok_or("Wrong file name.")?
+    ));
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut resp = send(
+        &[url.to_string()],
+        &mut 0,
+        &options.retry,
+        &options.cancel,
+        |u| client.get(u).send(),
+    )?;
+
+    let mut file = File::create(&tmp)?;
+    std::io::copy(&mut resp, &mut file)?;
+    drop(file);
+
+    if options.cancel.is_cancelled() {
+        fs::remove_file(&tmp)?;
+        return Err(Cancelled.into());
+    }
+
+    if !verify(&tmp, size, options.checksum.as_ref())? {
+        error!(target: DOWNLOADER, "Checksum mismatch for \"{url}\".");
+        fs::remove_file(&tmp)?;
+        return Err(ChecksumMismatch {
+            file: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+        }
+        .into());
+    }
+
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::SystemTime,
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::{constants::LAUNCHER_DIR, hash::sha1_file};
+
+pub const HASH_CACHE: &str = "HASH_CACHE";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedHash {
+    size: u64,
+    modified: SystemTime,
+    sha1: String,
+}
+
+// Sha1 of files keyed by path, size and modification time. File is hashed again only when it
+// was changed since last check.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HashCache {
+    files: HashMap<PathBuf, CachedHash>,
+}
+
+impl HashCache {
+    fn path() -> Result<PathBuf, Box<dyn Error>> {
+        let path = dirs::data_dir()
+            .ok_or("OS data dir not found.")?
+            .join(LAUNCHER_DIR);
+
+        if !path.exists() {
+            fs::create_dir_all(&path)?;
+        }
+
+        Ok(path.join("hash_cache.json"))
+    }
+
+    // Broken or missing cache is the same as empty one
+    pub fn load() -> Self {
+        let data = match Self::path().and_then(|p| Ok(fs::read_to_string(p)?)) {
+            Ok(d) => d,
+            Err(_) => return Self::default(),
+        };
+
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!(target: HASH_CACHE, "Couldn't parse hash cache, files will be rehashed. Error: {e}");
+            Self::default()
+        })
+    }
+
+    // Files removed since last check are dropped
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.files.retain(|path, _| path.is_file());
+        fs::write(Self::path()?, serde_json::to_string(self)?)?;
+        debug!(target: HASH_CACHE, "Saved {} hashes.", self.files.len());
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    fn get(&self, path: &Path, size: u64, modified: SystemTime) -> Option<String> {
+        self.files
+            .get(path)
+            .filter(|c| c.size == size && c.modified == modified)
+            .map(|c| c.sha1.clone())
+    }
+
+    // Sha1 of every file, in order. Changed files are hashed in parallel.
+    pub fn sha1_files(&mut self, paths: &[PathBuf]) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut ret = vec![String::new(); paths.len()];
+        let mut changed = Vec::new();
+
+        for (i, path) in paths.iter().enumerate() {
+            let meta = fs::metadata(path)?;
+            let (size, modified) = (meta.len(), meta.modified()?);
+            match self.get(path, size, modified) {
+                Some(sha1) => ret[i] = sha1,
+                None => changed.push((i, size, modified)),
+            }
+        }
+
+        if changed.is_empty() {
+            return Ok(ret);
+        }
+
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4)
+            .min(changed.len());
+        info!(
+            target: HASH_CACHE,
+            "Hashing {} of {} files with {workers} threads...",
+            changed.len(),
+            paths.len()
+        );
+
+        let next = AtomicUsize::new(0);
+        let hashed = Mutex::new(Vec::new());
+        let error = Mutex::new(None);
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| {
+                    while let Some(&(i, size, modified)) =
+                        changed.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        match sha1_file(&paths[i]) {
+                            Ok(sha1) => hashed.lock().unwrap().push((i, size, modified, sha1)),
+                            Err(e) => {
+                                *error.lock().unwrap() = Some(e);
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        if let Some(e) = error.into_inner().unwrap() {
+            return Err(e.into());
+        }
+
+        for (i, size, modified, sha1) in hashed.into_inner().unwrap() {
+            self.files.insert(
+                paths[i].clone(),
+                CachedHash {
+                    size,
+                    modified,
+                    sha1: sha1.clone(),
+                },
+            );
+            ret[i] = sha1;
+        }
+
+        Ok(ret)
+    }
+}
@@ -0,0 +1,42 @@
+use std::error::Error;
+
+use log::{error, warn};
+use reqwest::{blocking::ClientBuilder, Proxy};
+
+use crate::launcher::config::{AppConfig, ProxyConfig, ProxyMode};
+
+pub const HTTP: &str = "HTTP";
+
+// Proxy settings from launcher config
+pub fn proxy_config() -> ProxyConfig {
+    match AppConfig::get_config() {
+        Ok(config) => config.proxy,
+        Err(e) => {
+            warn!(target: HTTP, "Couldn't load config, using direct connection. Error: {e}");
+            ProxyConfig::default()
+        }
+    }
+}
+
+pub fn apply_proxy(
+    builder: ClientBuilder,
+    proxy: &ProxyConfig,
+) -> Result<ClientBuilder, Box<dyn Error>> {
+    Ok(match proxy.mode {
+        ProxyMode::None => builder.no_proxy(),
+        // Reqwest reads proxy environment variables by default
+        ProxyMode::System => builder,
+        ProxyMode::Manual => {
+            let url = proxy.url()?.ok_or("Proxy url is empty.")?;
+            builder.proxy(Proxy::all(url).map_err(|e| {
+                error!(target: HTTP, "Wrong proxy settings. Error: {e}");
+                e
+            })?)
+        }
+    })
+}
+
+// Every network client of launcher is built from this, so proxy settings apply everywhere
+pub fn client_builder() -> Result<ClientBuilder, Box<dyn Error>> {
+    apply_proxy(ClientBuilder::new(), &proxy_config())
+}
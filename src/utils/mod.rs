@@ -1,3 +1,15 @@
+pub mod archive;
+pub mod cache;
 pub mod constants;
+pub mod disk;
+pub mod download;
+pub mod hash;
+pub mod hash_cache;
+pub mod http;
 pub mod log;
 pub mod relaunch;
+pub mod signature;
+#[cfg(test)]
+pub mod test_helpers;
+#[cfg(test)]
+mod tests;
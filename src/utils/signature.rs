@@ -0,0 +1,59 @@
+use std::{error::Error, fmt, fs, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Signature, VerifyingKey, PUBLIC_KEY_LENGTH};
+use log::{error, info};
+
+pub const SIGNATURE: &str = "SIGNATURE";
+
+#[derive(Debug)]
+pub struct InvalidSignature {
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid signature: {}", self.reason)
+    }
+}
+
+impl Error for InvalidSignature {}
+
+fn invalid(reason: impl Into<String>) -> Box<dyn Error> {
+    let reason = reason.into();
+    error!(target: SIGNATURE, "Signature rejected: {reason}");
+    Box::new(InvalidSignature { reason })
+}
+
+pub fn public_key(hex: &str) -> Result<VerifyingKey, Box<dyn Error>> {
+    let hex = hex.trim();
+    if hex.len() != PUBLIC_KEY_LENGTH * 2 || !hex.is_ascii() {
+        return Err(invalid("wrong public key length"));
+    }
+
+    let mut bytes = [0; PUBLIC_KEY_LENGTH];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| invalid("public key is not hex"))?;
+    }
+
+    VerifyingKey::from_bytes(&bytes).map_err(|e| invalid(format!("bad public key, {e}")))
+}
+
+// Check detached base64 Ed25519 signature of whole file. Weak keys and malleable signatures
+// are rejected.
+pub fn verify_file(path: &Path, signature: &str, key: &VerifyingKey) -> Result<(), Box<dyn Error>> {
+    let signature = STANDARD
+        .decode(signature.trim())
+        .map_err(|e| invalid(format!("signature is not base64, {e}")))?;
+    let signature = Signature::from_slice(&signature)
+        .map_err(|_| invalid(format!("wrong signature length {}", signature.len())))?;
+
+    let data = fs::read(path)?;
+    key.verify_strict(&data, &signature)
+        .map_err(|_| invalid(format!("{:?} is not signed by release key", path)))?;
+
+    info!(target: SIGNATURE, "Signature of {:?} is valid.", path);
+
+    Ok(())
+}
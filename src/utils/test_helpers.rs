@@ -0,0 +1,249 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::Value;
+
+// Empty dir in OS temp dir, removed with everything inside when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("funnylauncher_{name}"));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+pub fn locale(name: &str) -> Value {
+    let data = fs::read_to_string(format!("locales/{name}.json")).unwrap();
+    serde_json::from_str(&data).unwrap()
+}
+
+// Content of "/<size>" served by `serve_files`
+pub fn served_body(size: usize) -> Vec<u8> {
+    (0..size).map(|i| i as u8).collect()
+}
+
+// Minimal keep-alive HTTP server. Every file is "/<size>" filled with its byte offsets, every
+// request takes `delay` like over a slow network.
+pub fn serve_files(delay: Duration) -> String {
+    fn handle(stream: TcpStream, delay: Duration) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let size: usize = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|p| p.trim_start_matches('/').parse().ok())
+                .unwrap_or(0);
+
+            let mut range = None;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some(r) = header.to_lowercase().strip_prefix("range: bytes=") {
+                    let (start, end) = r.trim().split_once('-').unwrap();
+                    range = Some((
+                        start.parse::<usize>().unwrap(),
+                        end.parse::<usize>().unwrap(),
+                    ));
+                }
+            }
+
+            std::thread::sleep(delay);
+
+            let body = served_body(size);
+            let head = match range {
+                Some((start, end)) => format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {start}-{end}/{size}\r\n\r\n",
+                    end - start + 1
+                ),
+                None => format!("HTTP/1.1 200 OK\r\nContent-Length: {size}\r\n\r\n"),
+            };
+            let (start, end) = range.unwrap_or((0, size.max(1) - 1));
+            stream.write_all(head.as_bytes()).unwrap();
+            if size > 0 {
+                stream.write_all(&body[start..=end]).unwrap();
+            }
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            std::thread::spawn(move || handle(stream, delay));
+        }
+    });
+
+    format!("http://{addr}")
+}
+
+// Request seen by `ResumableServer`: Range and If-Range headers
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeRequest {
+    pub range: Option<String>,
+    pub if_range: Option<String>,
+}
+
+// Server of "/<size>" files like `serve_files`, which sends ETag and answers Range only when
+// If-Range matches it. Tests change ETag to simulate new content and cut transfers to simulate
+// broken connection.
+#[derive(Clone)]
+pub struct ResumableServer {
+    pub url: String,
+    pub etag: Arc<Mutex<String>>,
+    // Connection is closed when response reaches this file offset
+    pub cut_at: Arc<Mutex<Option<usize>>>,
+    pub requests: Arc<Mutex<Vec<RangeRequest>>>,
+}
+
+impl ResumableServer {
+    pub fn start(etag: &str) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = Self {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            etag: Arc::new(Mutex::new(etag.to_string())),
+            cut_at: Arc::new(Mutex::new(None)),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let state = server.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = state.clone();
+                std::thread::spawn(move || state.handle(stream));
+            }
+        });
+
+        server
+    }
+
+    // Ranged requests since last call
+    pub fn take_requests(&self) -> Vec<RangeRequest> {
+        std::mem::take(&mut *self.requests.lock().unwrap())
+            .into_iter()
+            .filter(|r| r.range.is_some())
+            .collect()
+    }
+
+    fn handle(&self, stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let size: usize = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|p| p.trim_start_matches('/').parse().ok())
+                .unwrap_or(0);
+
+            let mut request = RangeRequest {
+                range: None,
+                if_range: None,
+            };
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let (name, value) = header.split_once(':').unwrap();
+                match name.to_lowercase().as_str() {
+                    "range" => request.range = Some(value.trim().to_string()),
+                    "if-range" => request.if_range = Some(value.trim().to_string()),
+                    _ => (),
+                }
+            }
+            self.requests.lock().unwrap().push(request.clone());
+
+            let etag = self.etag.lock().unwrap().clone();
+            let range = match (&request.range, &request.if_range) {
+                (Some(r), None) => Some(r),
+                (Some(r), Some(tag)) if *tag == etag => Some(r),
+                _ => None,
+            }
+            .and_then(|r| r.strip_prefix("bytes="))
+            .and_then(|r| r.split_once('-'))
+            .map(|(start, end)| {
+                (
+                    start.parse::<usize>().unwrap(),
+                    end.parse::<usize>().unwrap(),
+                )
+            });
+
+            let head = match range {
+                Some((start, end)) => format!(
+                    "HTTP/1.1 206 Partial Content\r\nETag: {etag}\r\nContent-Length: {}\r\nContent-Range: bytes {start}-{end}/{size}\r\n\r\n",
+                    end - start + 1
+                ),
+                None => format!("HTTP/1.1 200 OK\r\nETag: {etag}\r\nContent-Length: {size}\r\n\r\n"),
+            };
+            let (start, end) = range.unwrap_or((0, size.max(1) - 1));
+            let cut = self
+                .cut_at
+                .lock()
+                .unwrap()
+                .filter(|c| (start..=end).contains(c));
+
+            // Client may close connection after headers, like when it asks for size only
+            let body = served_body(size);
+            let written = stream.write_all(head.as_bytes()).and_then(|_| match cut {
+                Some(cut) => stream.write_all(&body[start..cut]),
+                None if size > 0 => stream.write_all(&body[start..=end]),
+                None => Ok(()),
+            });
+            if written.is_err() || cut.is_some() {
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+                return;
+            }
+        }
+    }
+}
+
+// Address where nothing listens, like a mirror which is down
+pub fn dead_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
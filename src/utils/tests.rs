@@ -0,0 +1,504 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::launcher::commands::DownloadProgress;
+
+use super::{
+    archive::{extract, install, link_inside, recover, safe_join, swap},
+    cache::{clear_dir, dir_size},
+    disk::{ensure_space, free_space, NotEnoughSpace},
+    download::{
+        download, is_cancelled, Checksum, ChecksumMismatch, DownloadOptions, ResumeState,
+        RetryPolicy, SpeedMeter,
+    },
+    hash::hex,
+    hash_cache::HashCache,
+    signature::{public_key, verify_file, InvalidSignature},
+    test_helpers::{dead_url, serve_files, served_body, ResumableServer, TempDir},
+};
+
+#[test]
+fn resume_offset_test() {
+    let state = ResumeState {
+        url: "http://localhost/game.zip".to_string(),
+        size: 1000,
+        etag: Some("\"v1\"".to_string()),
+        downloaded: 400,
+    };
+    let url = "http://localhost/game.zip";
+
+    assert_eq!(state.offset(url, 1000, Some("\"v1\""), 400), 400);
+    // File is longer than confirmed, tail is rewritten
+    assert_eq!(state.offset(url, 1000, Some("\"v1\""), 500), 400);
+    // Content changed
+    assert_eq!(state.offset(url, 1000, Some("\"v2\""), 400), 0);
+    assert_eq!(state.offset(url, 1200, Some("\"v1\""), 400), 0);
+    // Without ETag it's impossible to check content
+    assert_eq!(state.offset(url, 1000, None, 400), 0);
+    // File was truncated
+    assert_eq!(state.offset(url, 1000, Some("\"v1\""), 100), 0);
+    assert_eq!(
+        state.offset("http://localhost/other.zip", 1000, Some("\"v1\""), 400),
+        0
+    );
+}
+
+#[test]
+fn download_test() {
+    let url = serve_files(Duration::ZERO);
+    let dir = TempDir::new("download_test");
+    let path = dir.join("game.zip");
+
+    let body: Vec<u8> = served_body(100000);
+    let sha256 = {
+        use sha2::{Digest, Sha256};
+        hex(&Sha256::digest(&body))
+    };
+
+    let client = reqwest::blocking::Client::new();
+    let options = DownloadOptions {
+        chunk_size: 16 * 1024,
+        checksum: Some(Checksum::Sha256(sha256)),
+        ..Default::default()
+    };
+    let mut chunks = 0;
+    download(&client, &[format!("{url}/100000")], &path, &options, |p| {
+        assert_eq!(p.total_bytes, 100000);
+        assert_eq!(p.file, "game.zip");
+        chunks += 1;
+        Ok(())
+    })
+    .unwrap();
+    // Every chunk and completion
+    assert_eq!(chunks, 8);
+    assert_eq!(fs::read(&path).unwrap(), body);
+
+    // Corrupted download is removed
+    let options = DownloadOptions {
+        checksum: Some(Checksum::Sha256("0".repeat(64))),
+        ..Default::default()
+    };
+    let e = download(&client, &[format!("{url}/100000")], &path, &options, |_| {
+        Ok(())
+    })
+    .unwrap_err();
+    assert!(e.is::<ChecksumMismatch>());
+    assert!(!path.exists());
+
+    let options = DownloadOptions::default();
+    options.cancel.cancel();
+    let e = download(&client, &[format!("{url}/100000")], &path, &options, |_| {
+        Ok(())
+    })
+    .unwrap_err();
+    assert!(is_cancelled(e.as_ref()));
+}
+
+#[test]
+fn download_cancel_test() {
+    let url = serve_files(Duration::from_millis(20));
+    let dir = TempDir::new("download_cancel_test");
+    let client = reqwest::blocking::Client::new();
+
+    // Paused download waits for resume
+    let options = DownloadOptions {
+        chunk_size: 16 * 1024,
+        ..Default::default()
+    };
+    options.cancel.pause();
+    let cancel = options.cancel.clone();
+    let resume = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        cancel.resume();
+    });
+    let start = Instant::now();
+    download(
+        &client,
+        &[format!("{url}/50000")],
+        &dir.join("paused.zip"),
+        &options,
+        |_| Ok(()),
+    )
+    .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    resume.join().unwrap();
+
+    // Partial archive and its resume state are removed
+    let path = dir.join("game.zip");
+    let options = DownloadOptions {
+        chunk_size: 16 * 1024,
+        ..Default::default()
+    };
+    let e = download(&client, &[format!("{url}/100000")], &path, &options, |p| {
+        if p.bytes > 0 {
+            options.cancel.cancel();
+        }
+        Ok(())
+    })
+    .unwrap_err();
+    assert!(is_cancelled(e.as_ref()));
+    assert!(!path.exists());
+    assert!(ResumeState::load(&path).is_none());
+
+    // Aborted download is kept for the next run
+    let options = DownloadOptions {
+        chunk_size: 16 * 1024,
+        ..Default::default()
+    };
+    let e = download(&client, &[format!("{url}/100000")], &path, &options, |p| {
+        if p.bytes > 0 {
+            options.cancel.abort();
+        }
+        Ok(())
+    })
+    .unwrap_err();
+    assert!(is_cancelled(e.as_ref()));
+    let state = ResumeState::load(&path).unwrap();
+    assert!(state.downloaded > 0);
+    assert_eq!(fs::metadata(&path).unwrap().len(), state.downloaded);
+}
+
+#[test]
+fn download_resume_test() {
+    let server = ResumableServer::start("\"v1\"");
+    let dir = TempDir::new("download_resume_test");
+    let client = reqwest::blocking::Client::new();
+    let body = served_body(100000);
+    let sha256 = {
+        use sha2::{Digest, Sha256};
+        hex(&Sha256::digest(&body))
+    };
+    let options = DownloadOptions {
+        chunk_size: 16 * 1024,
+        checksum: Some(Checksum::Sha256(sha256)),
+        retry: RetryPolicy {
+            attempts: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let urls = vec![format!("{}/100000", server.url)];
+
+    for (path, etag, resumed_from) in [
+        // Connection is broken in third chunk, two chunks are kept
+        (dir.join("same.zip"), "\"v1\"", 32768),
+        // New content on server, nothing could be reused
+        (dir.join("changed.zip"), "\"v2\"", 0),
+    ] {
+        *server.cut_at.lock().unwrap() = Some(40000);
+        assert!(download(&client, &urls, &path, &options, |_| Ok(())).is_err());
+        assert_eq!(ResumeState::load(&path).unwrap().downloaded, 32768);
+        server.take_requests();
+
+        *server.cut_at.lock().unwrap() = None;
+        *server.etag.lock().unwrap() = etag.to_string();
+        download(&client, &urls, &path, &options, |_| Ok(())).unwrap();
+
+        let first = server.take_requests().remove(0);
+        assert_eq!(
+            first.range,
+            Some(format!("bytes={resumed_from}-{}", resumed_from + 16383))
+        );
+        assert_eq!(first.if_range.as_deref(), Some(etag));
+        assert_eq!(fs::read(&path).unwrap(), body);
+        assert!(ResumeState::load(&path).is_none());
+    }
+}
+
+#[test]
+fn retry_test() {
+    let policy = RetryPolicy {
+        attempts: 5,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+    };
+    assert_eq!(policy.delay(1, 0.), Duration::from_millis(50));
+    assert_eq!(policy.delay(1, 1.), Duration::from_millis(100));
+    assert_eq!(policy.delay(3, 1.), Duration::from_millis(400));
+    // Capped by max delay
+    assert_eq!(policy.delay(10, 1.), Duration::from_millis(1000));
+    assert!(policy.delay(10, 0.) >= Duration::from_millis(500));
+
+    // First mirror is down, file comes from the second one
+    let dead = dead_url();
+    let alive = serve_files(Duration::ZERO);
+    let dir = TempDir::new("retry_test");
+    let path = dir.join("game.zip");
+
+    let options = DownloadOptions {
+        chunk_size: 16 * 1024,
+        retry: RetryPolicy {
+            attempts: 2,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+        },
+        ..Default::default()
+    };
+    let urls = vec![format!("{dead}/50000"), format!("{alive}/50000")];
+    download(
+        &reqwest::blocking::Client::new(),
+        &urls,
+        &path,
+        &options,
+        |_| Ok(()),
+    )
+    .unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), 50000);
+}
+
+#[test]
+fn speed_meter_test() {
+    let mut meter = SpeedMeter::new(0);
+    let start = Instant::now();
+    let near = |a: f64, b: f64| (a - b).abs() < 1.;
+    assert!(near(
+        meter.update_at(1000, start + Duration::from_secs(1)),
+        1000.
+    ));
+    // Too short interval doesn't change speed
+    let speed = meter.update_at(5000, start + Duration::from_millis(1010));
+    assert!(near(speed, 1000.));
+    // Smoothed: 0.3 * 3000 + 0.7 * 1000
+    assert!(near(
+        meter.update_at(4000, start + Duration::from_secs(2)),
+        1600.
+    ));
+}
+
+#[test]
+fn archive_extract_test() {
+    use zip::{write::FileOptions, ZipWriter};
+
+    let dir = TempDir::new("archive_test");
+
+    let zip = |name: &str, files: &[(&str, u32)]| {
+        let path = dir.join(name);
+        let mut zip = ZipWriter::new(fs::File::create(&path).unwrap());
+        for (file, mode) in files {
+            zip.start_file(*file, FileOptions::default().unix_permissions(*mode))
+                .unwrap();
+            zip.write_all(file.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    };
+
+    let base = Path::new("/game");
+    assert!(link_inside(base, Path::new("/game/libs"), "../mods/a.jar"));
+    assert!(link_inside(base, Path::new("/game"), "./options.txt"));
+    assert!(!link_inside(base, Path::new("/game"), "../etc/passwd"));
+    assert!(!link_inside(base, Path::new("/game"), "/etc/passwd"));
+    // ".." after other components could go through another symlink
+    assert!(!link_inside(base, Path::new("/game"), "libs/../.."));
+
+    let base = Path::new("/games/pack");
+    assert_eq!(
+        safe_join(base, "config/jei.toml").unwrap(),
+        base.join("config").join("jei.toml")
+    );
+    assert!(safe_join(base, "../../etc/passwd").is_err());
+    assert!(safe_join(base, "/etc/passwd").is_err());
+
+    // Zip slip
+    let evil = zip("evil.zip", &[("ok.txt", 0o644), ("../evil.txt", 0o644)]);
+    assert!(extract(&evil, &dir.join("evil"), |_| Ok(())).is_err());
+    assert!(!dir.join("evil.txt").exists());
+
+    let game = dir.join(".funnycraft");
+    fs::create_dir_all(game.join("saves/world")).unwrap();
+    fs::create_dir_all(game.join("mods")).unwrap();
+    fs::write(game.join("saves/world/level.dat"), "world").unwrap();
+    fs::write(game.join("mods/old.jar"), "old").unwrap();
+    fs::write(game.join("mods/user.jar"), "user").unwrap();
+    fs::create_dir_all(game.join("shaderpacks")).unwrap();
+    fs::write(game.join("shaderpacks/a.zip"), "shader").unwrap();
+
+    let archive = zip(
+        "game.zip",
+        &[
+            (".funnycraft/run.sh", 0o755),
+            (".funnycraft/mods/new.jar", 0o644),
+        ],
+    );
+    let mut last = DownloadProgress::default();
+    install(
+        &archive,
+        &game,
+        &["saves", "logs", "mods/user.jar", "shaderpacks/a.zip"],
+        |p| {
+            last = p;
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!((last.files, last.total_files), (2, 2));
+    assert_eq!(last.bytes, last.total_bytes);
+    assert_eq!(
+        fs::read_to_string(game.join("saves/world/level.dat")).unwrap(),
+        "world"
+    );
+    assert!(game.join("mods/new.jar").exists());
+    assert!(!game.join("mods/old.jar").exists());
+    assert_eq!(
+        fs::read_to_string(game.join("mods/user.jar")).unwrap(),
+        "user"
+    );
+    // Parent dir missing in new package is created
+    assert!(game.join("shaderpacks/a.zip").exists());
+    assert!(!dir.join(".funnycraft.staging").exists());
+    assert!(!dir.join(".funnycraft.old").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(game.join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+}
+
+#[test]
+fn archive_swap_recover_test() {
+    let dir = TempDir::new("archive_swap_test");
+    let (game, old, new) = (dir.join("game"), dir.join("game.old"), dir.join("new"));
+
+    // Interrupted after game dir was moved away: it's restored before swap
+    fs::create_dir_all(old.join("saves/world")).unwrap();
+    fs::write(old.join("saves/world/level.dat"), "world").unwrap();
+    fs::create_dir_all(&new).unwrap();
+    fs::write(new.join("game.jar"), "v2").unwrap();
+    swap(&new, &game, &["saves"]).unwrap();
+    assert_eq!(
+        fs::read_to_string(game.join("saves/world/level.dat")).unwrap(),
+        "world"
+    );
+    assert!(game.join("game.jar").exists());
+    assert!(!old.exists());
+
+    // Interrupted while moving kept paths: they are moved into new game dir
+    fs::rename(game.join("saves"), dir.join("saves")).unwrap();
+    fs::create_dir_all(&old).unwrap();
+    fs::rename(dir.join("saves"), old.join("saves")).unwrap();
+    recover(&game, &["saves"]).unwrap();
+    assert!(game.join("saves/world/level.dat").exists());
+    assert!(game.join("game.jar").exists());
+    assert!(!old.exists());
+}
+
+#[test]
+fn cache_clear_test() {
+    let dir = TempDir::new("cache_test");
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("funnycraft.zip"), vec![0; 1000]).unwrap();
+    fs::write(dir.join("funnycraft.zip.resume.json"), "{}").unwrap();
+    fs::write(dir.join("nested/part"), vec![0; 500]).unwrap();
+
+    assert_eq!(dir_size(&dir).unwrap(), 1502);
+    assert_eq!(clear_dir(&dir).unwrap(), 1502);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    assert_eq!(clear_dir(&dir).unwrap(), 0);
+}
+
+#[test]
+fn disk_space_test() {
+    let dir = std::env::temp_dir();
+    let free = free_space(&dir).unwrap();
+    assert!(free > 0);
+    // Not created yet dir is checked on its parent filesystem
+    assert!(free_space(&dir.join("funnylauncher_missing/game")).is_ok());
+
+    assert!(ensure_space(&dir, 0).is_ok());
+    let e = ensure_space(&dir, u64::MAX).unwrap_err();
+    let space = e.downcast_ref::<NotEnoughSpace>().unwrap();
+    assert_eq!(space.required, u64::MAX);
+}
+
+#[test]
+fn hash_cache_test() {
+    use sha1::{Digest, Sha1};
+
+    let dir = TempDir::new("hash_cache_test");
+    let paths: Vec<_> = (0..20).map(|i| dir.join(format!("{i}.jar"))).collect();
+    for (i, path) in paths.iter().enumerate() {
+        fs::write(path, format!("file {i:02}")).unwrap();
+    }
+
+    let mut cache = HashCache::default();
+    let hashes = cache.sha1_files(&paths).unwrap();
+    assert_eq!(cache.len(), 20);
+    assert_eq!(hashes[3], hex(&Sha1::digest(b"file 03")));
+
+    // Same size and modification time, so file is not read again
+    let modified = fs::metadata(&paths[3]).unwrap().modified().unwrap();
+    fs::write(&paths[3], "file XX").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&paths[3])
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert_eq!(cache.sha1_files(&paths).unwrap(), hashes);
+
+    // Changed modification time is rehashed
+    fs::File::options()
+        .write(true)
+        .open(&paths[3])
+        .unwrap()
+        .set_modified(modified + Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(
+        cache.sha1_files(&paths[3..4]).unwrap()[0],
+        hex(&Sha1::digest(b"file XX"))
+    );
+
+    // Full re-verify starts from empty cache
+    assert_eq!(
+        HashCache::default().sha1_files(&paths).unwrap()[3],
+        hex(&Sha1::digest(b"file XX"))
+    );
+}
+
+#[test]
+fn launcher_signature_test() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let dir = TempDir::new("signature_test");
+    let path = dir.join("funnylauncher.exe");
+    fs::write(&path, b"launcher binary").unwrap();
+
+    let signing = SigningKey::from_bytes(&[7; 32]);
+    let key = public_key(&hex(signing.verifying_key().as_bytes())).unwrap();
+    let signature = STANDARD.encode(signing.sign(b"launcher binary").to_bytes());
+    verify_file(&path, &format!("{signature}\n"), &key).unwrap();
+
+    let rejected = |signature: &str| {
+        verify_file(&path, signature, &key)
+            .unwrap_err()
+            .is::<InvalidSignature>()
+    };
+    // Tampered binary, other key, garbage and missing signature
+    fs::write(&path, b"launcher binarY").unwrap();
+    assert!(rejected(&signature));
+    fs::write(&path, b"launcher binary").unwrap();
+    let other = SigningKey::from_bytes(&[8; 32]);
+    assert!(rejected(
+        &STANDARD.encode(other.sign(b"launcher binary").to_bytes())
+    ));
+    assert!(rejected("not base64!"));
+    assert!(rejected(""));
+
+    assert!(public_key("00").is_err());
+
+    // Signature made by openssl as described in RELEASING.md
+    let fixture = Path::new("tests_file/update_signature");
+    let key = public_key(&fs::read_to_string(fixture.join("release.pub")).unwrap()).unwrap();
+    let signature = fs::read_to_string(fixture.join("funnylauncher.sig")).unwrap();
+    verify_file(&fixture.join("funnylauncher"), &signature, &key).unwrap();
+}